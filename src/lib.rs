@@ -1,78 +1,445 @@
+//! Simple line-column tracking utils.
+//!
+//! # Position model
+//!
+//! [`LineCache`] and [`PosTrackerExtern`] agree on the following model:
+//!
+//! * A line starts at offset 0 or directly after a line terminator
+//!   (see [`LineTerminators`]), and ends after its own terminator.
+//!   A terminator thus belongs to the line it ends, and a multi-byte
+//!   terminator like `\r\n` only ever counts as a single line break.
+//! * The end of the input is a valid position, in the last line.
+//! * Lines and columns are zero-based. The column of an offset is the
+//!   number of columns (in the chosen [`ColumnUnit`]) occupied by the bytes
+//!   from the start of its line up to the offset. Every byte takes part in
+//!   that, including a `\r` which isn't part of a line terminator.
 #![no_std]
 
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 use alloc::vec::Vec;
+use core::ops::Range;
 
 // SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
 
+mod check;
+pub mod lsp;
+mod position;
+pub mod render;
+mod source_map;
+pub mod srcmap_v3;
+mod stream;
+mod terminator;
+mod unit;
+pub use check::{check_consistency, Mismatch};
+pub use position::{IndexBase, Position, Span};
+pub use source_map::{FileId, SourceFile, SourceMap};
+#[cfg(feature = "std")]
+pub use stream::TrackingReader;
+pub use stream::{PosTrackerStream, TrackingWriter};
+pub use terminator::LineTerminators;
+pub use unit::{next_tab_stop, ColumnUnit};
+
 /// A pre-computed line cache, caching
 /// line ending offsets to speed up later line:col computations
 #[derive(Clone, Debug)]
-pub struct LineCache(Vec<(usize, usize)>);
+pub struct LineCache {
+    /// `(start, end)` offsets of each line terminator
+    breaks: Vec<(usize, usize)>,
+    len: usize,
+    terminators: LineTerminators,
+}
 
 impl LineCache {
+    /// creates a line cache which only treats `\n` as line terminator
+    #[inline]
     pub fn new(s: &str) -> Self {
-        Self(
-            s.bytes()
-                .enumerate()
-                .filter(|&(_, i)| i == b'\n')
-                .enumerate()
-                .map(|(lnr, (bkpt, _))| (lnr + 1, bkpt))
-                .collect(),
-        )
+        Self::with_terminators(s, LineTerminators::Lf)
+    }
+
+    pub fn with_terminators(s: &str, terminators: LineTerminators) -> Self {
+        Self {
+            breaks: terminators.find_iter(s.as_bytes()).collect(),
+            len: s.len(),
+            terminators,
+        }
+    }
+
+    #[inline(always)]
+    pub fn terminators(&self) -> LineTerminators {
+        self.terminators
     }
 
-    /// returns the zero-based (line, col) information
+    /// updates the cache after the bytes in `range` of the cached input
+    /// were replaced, so that it matches `new_src`, the input after the edit.
+    ///
+    /// Only the edited region and the rest of its last line are scanned again,
+    /// the line breaks after it are just shifted.
+    ///
+    /// # Panics
+    /// Panics if `range` is out of bounds of the cached input, or if
+    /// `new_src` is too short to be the result of this edit.
+    pub fn apply_edit(&mut self, range: Range<usize>, new_src: &str) {
+        assert!(range.start <= range.end && range.end <= self.len);
+        let new_end = (new_src.len() + range.end)
+            .checked_sub(self.len)
+            .filter(|&new_end| new_end >= range.start)
+            .expect("edit doesn't match the new input");
+        let shift = |i: usize| i - range.end + new_end;
+
+        // terminators are at most 3 bytes long, so the edit may merge with
+        // or split a terminator starting up to 2 bytes in front of it.
+        let lo = range.start.saturating_sub(2);
+        let first = self.breaks.partition_point(|&(_, end)| end <= lo);
+        let scan_start = self
+            .breaks
+            .get(first)
+            .map_or(lo, |&(start, _)| start.min(lo));
+        // breaks starting after the edit might be unchanged
+        let mut tail = self.breaks.partition_point(|&(start, _)| start < range.end);
+
+        let mut found = Vec::new();
+        let mut synced = false;
+        for (start, end) in self
+            .terminators
+            .find_iter(&new_src.as_bytes()[scan_start..])
+            .map(|(start, end)| (scan_start + start, scan_start + end))
+        {
+            if start >= new_end {
+                // scanning from here on yields the same breaks as before
+                // once we meet one of them again.
+                while self
+                    .breaks
+                    .get(tail)
+                    .is_some_and(|&(old, _)| shift(old) < start)
+                {
+                    tail += 1;
+                }
+                if self.breaks.get(tail).map(|&(s, e)| (shift(s), shift(e))) == Some((start, end)) {
+                    synced = true;
+                    break;
+                }
+            }
+            found.push((start, end));
+        }
+        if !synced {
+            // we scanned up to the end of the input
+            tail = self.breaks.len();
+        }
+
+        for brk in &mut self.breaks[tail..] {
+            *brk = (shift(brk.0), shift(brk.1));
+        }
+        self.breaks.splice(first..tail, found);
+        self.len = new_src.len();
+    }
+
+    /// returns the number of lines, which is always at least 1
+    #[inline]
+    pub fn line_count(&self) -> usize {
+        self.breaks.len() + 1
+    }
+
+    /// returns the offset of the first byte of `line`,
+    /// or `None` if `line` is past the end of the input
+    #[inline]
+    pub fn line_start(&self, line: usize) -> Option<usize> {
+        match line {
+            0 => Some(0),
+            _ => self.breaks.get(line - 1).map(|&(_, end)| end),
+        }
+    }
+
+    /// returns the offsets of the contents of `line`, without its terminator,
+    /// or `None` if `line` is past the end of the input
+    pub fn line_range(&self, line: usize) -> Option<Range<usize>> {
+        let start = self.line_start(line)?;
+        let end = match self.breaks.get(line) {
+            Some(&(end, _)) => end,
+            None => self.len,
+        };
+        Some(start..end)
+    }
+
+    /// returns the zero-based (line, col) information,
+    /// with the column counted in bytes
+    ///
+    /// This is a binary search over the cached line breaks,
+    /// so it takes `O(log lines)` time.
     pub fn run(&self, pos: usize) -> (usize, usize) {
+        let (lnr, start) = self.lookup(pos);
+        (lnr, pos - start)
+    }
+
+    /// like [`run`](Self::run), but counts the column in the given `unit`.
+    ///
+    /// `src` has to be the string this cache was created from,
+    /// and `pos` has to lie on a `char` boundary of it.
+    pub fn run_with(&self, src: &str, pos: usize, unit: ColumnUnit) -> (usize, usize) {
+        let (lnr, start) = self.lookup(pos);
+        (lnr, unit.count(&src.as_bytes()[start..pos]))
+    }
+
+    /// like [`run_with`](Self::run_with), but expands tabs to the next
+    /// multiple of `tab_width` (see [`next_tab_stop`]),
+    /// which yields the column an editor would display.
+    ///
+    /// Returns `(line, visual column, byte column)`, where the byte column
+    /// is the same as returned by [`run`](Self::run).
+    pub fn run_tabbed(
+        &self,
+        src: &str,
+        pos: usize,
+        unit: ColumnUnit,
+        tab_width: usize,
+    ) -> (usize, usize, usize) {
+        let (lnr, start) = self.lookup(pos);
+        let col = unit.advance(&src.as_bytes()[start..pos], 0, tab_width);
+        (lnr, col, pos - start)
+    }
+
+    /// returns the line `pos` lies in, and the offset of the first byte of it.
+    fn lookup(&self, pos: usize) -> (usize, usize) {
         // if the line cache returns e.g. lnr=1, the line 0 ends
         // before our position, so we are in line 1. etc.
-        let (lnr, bkpt) = self
-            .0
-            .iter()
-            .copied()
-            .take_while(|&(_, bkpt)| bkpt <= pos)
-            .last()
-            .unwrap_or((0, 0));
-        (lnr, pos - bkpt)
+        // `self.breaks` is sorted, so the predicate is monotonic.
+        match self.breaks.partition_point(|&(_, end)| end <= pos) {
+            0 => (0, 0),
+            n => (n, self.breaks[n - 1].1),
+        }
+    }
+
+    /// returns the start of `line`, the end of its contents, and the end of
+    /// the half-open range of offsets which [`run`](Self::run) maps into `line`;
+    /// the end of the input counts as part of the last line.
+    fn line_bounds(&self, line: usize) -> Option<(usize, usize, usize)> {
+        let start = self.line_start(line)?;
+        Some(match self.breaks.get(line) {
+            Some(&(content_end, end)) => (start, content_end, end),
+            None => (start, self.len, self.len + 1),
+        })
+    }
+
+    /// the inverse of [`run`](Self::run): returns the offset of the zero-based
+    /// (line, col) position, or `None` if `line` is past the end of the input
+    /// or `col` is past the end of the line (including its terminator).
+    ///
+    /// For every `pos <= len` of the cached input,
+    /// `offset(run(pos)) == Some(pos)` holds.
+    pub fn offset(&self, line: usize, col: usize) -> Option<usize> {
+        let (start, _, end) = self.line_bounds(line)?;
+        let pos = start.checked_add(col)?;
+        if pos < end {
+            Some(pos)
+        } else {
+            None
+        }
+    }
+
+    /// like [`offset`](Self::offset), but `col` is counted in the given `unit`.
+    ///
+    /// `src` has to be the string this cache was created from.
+    /// Returns `None` if `col` points into the middle of a character
+    /// (e.g. between the two halves of a UTF-16 surrogate pair, or between
+    /// the columns of a wide character), too.
+    pub fn offset_with(
+        &self,
+        src: &str,
+        line: usize,
+        col: usize,
+        unit: ColumnUnit,
+    ) -> Option<usize> {
+        self.offset_in(src, line, col, unit).ok()
+    }
+
+    /// like [`offset_clamped`](Self::offset_clamped), but `col` is counted
+    /// in the given `unit`. A `col` pointing into the middle of a character
+    /// is rounded down to the start of that character.
+    pub fn offset_clamped_with(
+        &self,
+        src: &str,
+        line: usize,
+        col: usize,
+        unit: ColumnUnit,
+    ) -> usize {
+        self.offset_in(src, line, col, unit)
+            .unwrap_or_else(|clamped| clamped)
+    }
+
+    /// returns either the exact offset, or the clamped offset as error.
+    fn offset_in(
+        &self,
+        src: &str,
+        line: usize,
+        col: usize,
+        unit: ColumnUnit,
+    ) -> Result<usize, usize> {
+        let (start, content_end, end) = self.line_bounds(line).ok_or(self.len)?;
+        let mut acc = 0;
+        let mut pos = start;
+        for (len, w) in unit.segments(&src[start..end.min(self.len)]) {
+            if acc >= col {
+                break;
+            }
+            if acc + w > col {
+                return Err(pos.min(content_end));
+            }
+            acc += w;
+            pos += len;
+        }
+        if acc == col && pos < end {
+            Ok(pos)
+        } else {
+            Err(pos.min(content_end))
+        }
+    }
+
+    /// like [`offset`](Self::offset), but never fails:
+    /// a `col` past the end of the line is clamped to the end of that line
+    /// (before its terminator), and a `line` past the end of the input
+    /// is clamped to the end of the input.
+    pub fn offset_clamped(&self, line: usize, col: usize) -> usize {
+        match self.line_bounds(line) {
+            Some((start, content_end, _)) => start.saturating_add(col).min(content_end),
+            None => self.len,
+        }
     }
 }
 
 /// A position tracker, only useful if the requested offset information only
 /// proceeds forwards or the implied failure at backwards moves is just annoying,
 /// but not fatal.
+///
+/// Columns are counted in bytes by default, use [`with_unit`](Self::with_unit)
+/// to count them in another [`ColumnUnit`].
+///
+/// The tracker follows the same position model as [`LineCache`]
+/// (see the [crate documentation](crate)), which can be verified using
+/// [`check_consistency`].
 #[derive(Clone, Copy, Debug, Default)]
 pub struct PosTrackerExtern {
     offset: usize,
     line: usize,
     column: usize,
+    unit: ColumnUnit,
+    tab_width: usize,
+    terminators: LineTerminators,
+    /// offset of the first byte of the current line
+    line_start: usize,
+    /// offset from which the next update has to count columns, before
+    /// `offset` if the bytes in between (an incomplete `char`, or a cluster
+    /// which may still be extended) have to be counted again
+    pending: usize,
+    /// the column at `pending`
+    pending_column: usize,
 }
 
 impl PosTrackerExtern {
+    #[inline]
+    pub fn with_unit(unit: ColumnUnit) -> Self {
+        Self {
+            unit,
+            ..Default::default()
+        }
+    }
+
+    /// expand tabs to the next multiple of `tab_width` from now on,
+    /// see [`next_tab_stop`]. The default of 0 counts tabs as one column.
+    #[inline]
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width;
+    }
+
+    /// use the given line terminators from now on,
+    /// the default only treats `\n` as line terminator.
+    #[inline]
+    pub fn set_terminators(&mut self, terminators: LineTerminators) {
+        self.terminators = terminators;
+    }
+
+    /// returns the current offset
+    #[inline(always)]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// returns the zero-based current line
+    #[inline(always)]
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// returns the zero-based current column, counted in the configured
+    /// unit with tabs expanded
+    #[inline(always)]
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// returns the zero-based current column in bytes,
+    /// without any tab expansion
+    #[inline(always)]
+    pub fn byte_column(&self) -> usize {
+        self.offset - self.line_start
+    }
+
     // always give `dat` as an argument (but it's start address shouldn't change),
     // to prevent borrowing conflicts or such.
+    // `dat` may grow between calls, but if it ended with a `\r` which is then
+    // followed by a `\n`, the position between both can't be reported correctly.
     pub fn update<'a>(
         &mut self,
         dat: &'a [u8],
         new_offset: usize,
     ) -> Option<(&'a [u8], usize, usize)> {
         new_offset.checked_sub(self.offset)?;
-        let mut ldif = 0;
-        let mut cdif = 0;
         let slc = &dat[self.offset..new_offset];
-        for &i in slc {
-            if i == b'\n' {
-                cdif = 0;
-                ldif += 1;
-            } else if i != b'\r' {
-                cdif += 1;
-            }
+        if self.terminators.lone_cr()
+            && self.offset != 0
+            && self.line_start == self.offset
+            && dat[self.offset - 1] == b'\r'
+            && slc.first() == Some(&b'\n')
+        {
+            // `dat` grew since we counted a `\r` at its end as line break,
+            // and the `\n` completes it to a single `\r\n`.
+            self.line_start += 1;
         }
-        self.offset = new_offset;
-        self.line += ldif;
-        if ldif != 0 {
+        // terminators are at most 3 bytes long, so only the last 2 bytes
+        // before `self.offset` may contain one we didn't count yet.
+        // only terminators which end before `new_offset` are complete.
+        let scan_start = self.line_start.max(self.offset.saturating_sub(2));
+        let mut ldif = 0;
+        for (_, end) in self
+            .terminators
+            .find_iter(&dat[scan_start..])
+            .map(|(start, end)| (scan_start + start, scan_start + end))
+            .take_while(|&(start, _)| start < new_offset)
+            .filter(|&(_, end)| end <= new_offset)
+        {
+            ldif += 1;
+            self.line_start = end;
+        }
+        // only the part after the last terminator contributes to the column
+        if ldif != 0 || self.pending < self.line_start {
             self.column = 0;
+            self.pending = self.line_start;
+            self.pending_column = 0;
         }
-        self.column += cdif;
+        // the previous update might've stopped in the middle of a `char`
+        // or cluster, so count it again
+        let (column, pending, pending_column) = self.unit.advance_partial(
+            &dat[self.pending..new_offset],
+            self.pending_column,
+            self.tab_width,
+        );
+        self.pending += pending;
+        self.pending_column = pending_column;
+        // extending the last cluster never decreases the column
+        let cdif = column.saturating_sub(self.column);
+        self.offset = new_offset;
+        self.line += ldif;
+        self.column = column;
         Some((slc, ldif, cdif))
     }
 }
@@ -95,6 +462,24 @@ impl<'a> PosTrackerDatRef<'a> {
         }
     }
 
+    #[inline]
+    pub fn with_unit(dat: &'a [u8], unit: ColumnUnit) -> Self {
+        Self {
+            dat,
+            inner: PosTrackerExtern::with_unit(unit),
+        }
+    }
+
+    #[inline]
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.inner.set_tab_width(tab_width);
+    }
+
+    #[inline]
+    pub fn set_terminators(&mut self, terminators: LineTerminators) {
+        self.inner.set_terminators(terminators);
+    }
+
     #[inline(always)]
     pub fn inner(&self) -> PosTrackerExtern {
         self.inner
@@ -117,8 +502,280 @@ mod tests {
 Hurra!
 "#;
         let lc = LineCache::new(SRC);
-        assert_eq!(lc.0, alloc::vec![(1, 17), (2, 24)]);
+        assert_eq!(lc.breaks, alloc::vec![(17, 18), (24, 25)]);
         assert_eq!(lc.run(3), (0, 3));
-        assert_eq!(lc.run(20), (1, 3));
+        assert_eq!(lc.run(20), (1, 2));
+    }
+
+    #[test]
+    fn run_matches_linear_scan() {
+        const SRC: &str = "a\n\nbc\ndef\n\n\ng";
+        let lc = LineCache::new(SRC);
+        for pos in 0..=SRC.len() + 2 {
+            let (lnr, start) = lc
+                .breaks
+                .iter()
+                .enumerate()
+                .take_while(|&(_, &(_, end))| end <= pos)
+                .last()
+                .map_or((0, 0), |(i, &(_, end))| (i + 1, end));
+            assert_eq!(lc.run(pos), (lnr, pos - start), "pos = {}", pos);
+        }
+    }
+
+    #[test]
+    fn offset_roundtrip() {
+        const SRC: &str = "ab\n\ncde\n";
+        let lc = LineCache::new(SRC);
+        assert_eq!(lc.line_count(), 4);
+        for pos in 0..=SRC.len() {
+            let (line, col) = lc.run(pos);
+            assert_eq!(lc.offset(line, col), Some(pos), "pos = {}", pos);
+            assert_eq!(lc.offset_clamped(line, col), pos, "pos = {}", pos);
+        }
+    }
+
+    #[test]
+    fn offset_out_of_range() {
+        const SRC: &str = "ab\n\ncde";
+        let lc = LineCache::new(SRC);
+        // line 0 is "ab\n", clamping stops in front of the newline
+        assert_eq!(lc.offset(0, 2), Some(2));
+        assert_eq!(lc.offset(0, 3), None);
+        assert_eq!(lc.offset_clamped(0, 100), 2);
+        assert_eq!(lc.offset(1, 0), Some(3));
+        assert_eq!(lc.offset(1, 1), None);
+        // the last line extends to the end of the input
+        assert_eq!(lc.offset(2, 3), Some(SRC.len()));
+        assert_eq!(lc.offset(2, 4), None);
+        assert_eq!(lc.offset_clamped(2, 4), SRC.len());
+        assert_eq!(lc.offset(3, 0), None);
+        assert_eq!(lc.offset_clamped(3, 0), SRC.len());
+        assert_eq!(lc.offset_clamped(usize::MAX, usize::MAX), SRC.len());
+
+        let lc = LineCache::new("\nx");
+        assert_eq!(lc.offset(0, 0), Some(0));
+        assert_eq!(lc.offset_clamped(0, 5), 0);
+    }
+
+    #[test]
+    fn column_units() {
+        // "𝄞" is 4 bytes, 1 char, 2 UTF-16 code units
+        const SRC: &str = "aä!\n𝄞b";
+        let lc = LineCache::new(SRC);
+        assert_eq!(lc.run_with(SRC, 3, ColumnUnit::Byte), (0, 3));
+        assert_eq!(lc.run_with(SRC, 3, ColumnUnit::Char), (0, 2));
+        assert_eq!(lc.run_with(SRC, 9, ColumnUnit::Byte), (1, 4));
+        assert_eq!(lc.run_with(SRC, 9, ColumnUnit::Char), (1, 1));
+        assert_eq!(lc.run_with(SRC, 9, ColumnUnit::Utf16), (1, 2));
+
+        for unit in [ColumnUnit::Byte, ColumnUnit::Char, ColumnUnit::Utf16] {
+            for (pos, _) in SRC.char_indices().chain(core::iter::once((SRC.len(), ' '))) {
+                let (line, col) = lc.run_with(SRC, pos, unit);
+                assert_eq!(
+                    lc.offset_with(SRC, line, col, unit),
+                    Some(pos),
+                    "{:?} @ {}",
+                    unit,
+                    pos
+                );
+            }
+        }
+        // between the halves of the surrogate pair
+        assert_eq!(lc.offset_with(SRC, 1, 1, ColumnUnit::Utf16), None);
+        assert_eq!(lc.offset_clamped_with(SRC, 1, 1, ColumnUnit::Utf16), 5);
+        assert_eq!(
+            lc.offset_clamped_with(SRC, 1, 9, ColumnUnit::Utf16),
+            SRC.len()
+        );
+
+        let mut pt = PosTrackerDatRef::with_unit(SRC.as_bytes(), ColumnUnit::Utf16);
+        assert_eq!(pt.update(3).map(|(_, l, c)| (l, c)), Some((0, 2)));
+        assert_eq!(pt.update(9).map(|(_, l, c)| (l, c)), Some((1, 2)));
+        assert_eq!(pt.update(10).map(|(_, l, c)| (l, c)), Some((0, 1)));
+    }
+
+    #[cfg(feature = "unicode-segmentation")]
+    #[test]
+    fn grapheme_columns() {
+        // 'a' + combining diaeresis, then a flag made of two regional indicators
+        const SRC: &str = "x\na\u{308}\u{1F1E9}\u{1F1EA}b";
+        let lc = LineCache::new(SRC);
+        let unit = ColumnUnit::Grapheme;
+        assert_eq!(lc.run_with(SRC, 5, unit), (1, 1));
+        assert_eq!(lc.run_with(SRC, 13, unit), (1, 2));
+        assert_eq!(lc.offset_with(SRC, 1, 1, unit), Some(5));
+
+        // updates stopping inside of a cluster don't inflate the column
+        let mut pt = PosTrackerDatRef::with_unit(SRC.as_bytes(), unit);
+        assert_eq!(pt.update(3).map(|(_, l, c)| (l, c)), Some((1, 1)));
+        assert_eq!(pt.update(5).map(|(_, l, c)| (l, c)), Some((0, 0)));
+        assert_eq!(pt.update(9).map(|(_, l, c)| (l, c)), Some((0, 1)));
+        assert_eq!(pt.update(14).map(|(_, l, c)| (l, c)), Some((0, 1)));
+
+        // ... nor inside of a `char` which extends a cluster
+        let mut pt = PosTrackerDatRef::with_unit("a\u{308}b".as_bytes(), unit);
+        assert_eq!(pt.update(2).map(|(_, l, c)| (l, c)), Some((0, 1)));
+        assert_eq!(pt.update(3).map(|(_, l, c)| (l, c)), Some((0, 0)));
+        assert_eq!(pt.update(4).map(|(_, l, c)| (l, c)), Some((0, 1)));
+        assert_eq!(pt.inner().column(), 2);
+    }
+
+    #[test]
+    fn tab_expansion() {
+        const SRC: &str = "\tab\tc\n\tä\tx";
+        let lc = LineCache::new(SRC);
+        assert_eq!(lc.run_tabbed(SRC, 3, ColumnUnit::Byte, 4), (0, 6, 3));
+        assert_eq!(lc.run_tabbed(SRC, 4, ColumnUnit::Byte, 4), (0, 8, 4));
+        // the newline still belongs to line 0
+        assert_eq!(lc.run_tabbed(SRC, 5, ColumnUnit::Char, 4), (0, 9, 5));
+        assert_eq!(lc.run_tabbed(SRC, 7, ColumnUnit::Char, 4), (1, 4, 1));
+        assert_eq!(lc.run_tabbed(SRC, 10, ColumnUnit::Char, 4), (1, 8, 4));
+        assert_eq!(lc.run_tabbed(SRC, 10, ColumnUnit::Char, 0), (1, 3, 4));
+
+        let mut pt = PosTrackerDatRef::with_unit(SRC.as_bytes(), ColumnUnit::Char);
+        pt.set_tab_width(4);
+        assert_eq!(pt.update(2).map(|(_, l, c)| (l, c)), Some((0, 5)));
+        assert_eq!(pt.update(5).map(|(_, l, c)| (l, c)), Some((0, 4)));
+        assert_eq!((pt.inner().column(), pt.inner().byte_column()), (9, 5));
+        assert_eq!(pt.update(11).map(|(_, l, c)| (l, c)), Some((1, 9)));
+        assert_eq!(pt.inner().line(), 1);
+        assert_eq!((pt.inner().column(), pt.inner().byte_column()), (9, 5));
+    }
+
+    #[test]
+    fn line_terminators() {
+        const SRC: &str = "a\r\nb\rc\u{2028}d\ne";
+        let lines = |t| {
+            let lc = LineCache::with_terminators(SRC, t);
+            (lc.line_count(), lc.run(SRC.len()).0)
+        };
+        assert_eq!(lines(LineTerminators::Lf), (3, 2));
+        assert_eq!(lines(LineTerminators::LfCrLf), (3, 2));
+        assert_eq!(lines(LineTerminators::LfCrCrLf), (4, 3));
+        assert_eq!(lines(LineTerminators::Unicode), (5, 4));
+
+        for t in [
+            LineTerminators::Lf,
+            LineTerminators::LfCrLf,
+            LineTerminators::LfCrCrLf,
+            LineTerminators::Unicode,
+        ] {
+            let lc = LineCache::with_terminators(SRC, t);
+            // the line count must not depend on how the input is split,
+            // even if a `\r\n` or `\u{2028}` is split between updates
+            for step in 1..4 {
+                let mut pt = PosTrackerDatRef::new(SRC.as_bytes());
+                pt.set_terminators(t);
+                let mut offset = 0;
+                while offset < SRC.len() {
+                    offset = (offset + step).min(SRC.len());
+                    pt.update(offset).unwrap();
+                }
+                assert_eq!(pt.inner().line(), lc.line_count() - 1, "{:?} / {}", t, step);
+                assert_eq!(pt.inner().column(), 1, "{:?} / {}", t, step);
+            }
+        }
+    }
+
+    #[test]
+    fn split_chars() {
+        const SRC: &str = "漢字x\tä\u{308}😀\ny";
+        let lc = LineCache::new(SRC);
+        #[allow(unused_mut)]
+        let mut units = alloc::vec![ColumnUnit::Byte, ColumnUnit::Char, ColumnUnit::Utf16];
+        #[cfg(feature = "unicode-width")]
+        units.push(ColumnUnit::Width);
+        #[cfg(feature = "unicode-segmentation")]
+        units.push(ColumnUnit::Grapheme);
+        // the columns must not depend on how the input is split,
+        // even if updates stop in the middle of a `char`
+        for unit in units {
+            for step in 1..5 {
+                let mut pt = PosTrackerDatRef::with_unit(SRC.as_bytes(), unit);
+                pt.set_tab_width(4);
+                let mut offset = 0;
+                while offset < SRC.len() {
+                    offset = (offset + step).min(SRC.len());
+                    pt.update(offset).unwrap();
+                    if SRC.is_char_boundary(offset) {
+                        let (line, column, _) = lc.run_tabbed(SRC, offset, unit, 4);
+                        let inner = pt.inner();
+                        assert_eq!(
+                            (inner.line(), inner.column()),
+                            (line, column),
+                            "{:?} / {} @ {}",
+                            unit,
+                            step,
+                            offset
+                        );
+                    }
+                }
+            }
+        }
+
+        #[cfg(feature = "unicode-width")]
+        {
+            let mut pt =
+                PosTrackerDatRef::with_unit(b"\xE6\xBC\xA2\xE5\xAD\x97x", ColumnUnit::Width);
+            // the width of the incomplete `漢` isn't known yet
+            assert_eq!(pt.update(1).map(|(_, l, c)| (l, c)), Some((0, 0)));
+            assert_eq!(pt.update(7).map(|(_, l, c)| (l, c)), Some((0, 5)));
+            assert_eq!(
+                pt.inner().column(),
+                lc.run_with(SRC, 7, ColumnUnit::Width).1
+            );
+        }
+    }
+
+    #[test]
+    fn lone_cr_at_end_of_growing_input() {
+        let mut pt = PosTrackerExtern::default();
+        pt.set_terminators(LineTerminators::LfCrCrLf);
+        assert_eq!(pt.update(b"a\r", 2).map(|(_, l, c)| (l, c)), Some((1, 0)));
+        assert_eq!(
+            pt.update(b"a\r\nb", 4).map(|(_, l, c)| (l, c)),
+            Some((0, 1))
+        );
+        assert_eq!((pt.line(), pt.column(), pt.byte_column()), (1, 1, 1));
+    }
+
+    #[test]
+    fn apply_edits() {
+        // a simple LCG, to get reproducible pseudo-random edits
+        let mut seed = 0x2545_F491_u32;
+        let mut rand = |n: usize| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 8) as usize % n
+        };
+        const PIECES: [&str; 9] = ["a", "bc", "\n", "\r", "\r\n", "\u{2028}", "\u{85}", "ä", ""];
+        for t in [
+            LineTerminators::Lf,
+            LineTerminators::LfCrLf,
+            LineTerminators::LfCrCrLf,
+            LineTerminators::Unicode,
+        ] {
+            let mut src = alloc::string::String::from("ab\r\ncd\n\ref\r");
+            let mut lc = LineCache::with_terminators(&src, t);
+            for _ in 0..500 {
+                let boundaries: Vec<_> = src
+                    .char_indices()
+                    .map(|(i, _)| i)
+                    .chain(core::iter::once(src.len()))
+                    .collect();
+                let a = boundaries[rand(boundaries.len())];
+                let b = boundaries[rand(boundaries.len())];
+                let range = a.min(b)..a.max(b);
+                let mut text = alloc::string::String::new();
+                for _ in 0..rand(4) {
+                    text.push_str(PIECES[rand(PIECES.len())]);
+                }
+                src.replace_range(range.clone(), &text);
+                lc.apply_edit(range.clone(), &src);
+                let fresh = LineCache::with_terminators(&src, t);
+                assert_eq!(lc.breaks, fresh.breaks, "{:?} {:?} {:?}", t, range, src);
+                assert_eq!(lc.len, fresh.len);
+            }
+        }
     }
 }
@@ -0,0 +1,170 @@
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+use super::{Converter, PositionEncoding, Range};
+use crate::{LineCache, LineTerminators};
+use alloc::string::String;
+
+/// A change of a text document, like a `TextDocumentContentChangeEvent`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContentChange {
+    /// the replaced range, `None` replaces the whole document
+    pub range: Option<Range>,
+    pub text: String,
+}
+
+impl ContentChange {
+    /// creates a change replacing `range`
+    #[inline]
+    pub fn new(range: Range, text: impl Into<String>) -> Self {
+        Self {
+            range: Some(range),
+            text: text.into(),
+        }
+    }
+
+    /// creates a change replacing the whole document
+    #[inline]
+    pub fn full(text: impl Into<String>) -> Self {
+        Self {
+            range: None,
+            text: text.into(),
+        }
+    }
+}
+
+/// A text document, which keeps its [`LineCache`] up to date while
+/// changes are applied to it.
+///
+/// Lines are split at `\n`, `\r\n` and `\r`, like the protocol specifies.
+#[derive(Clone, Debug)]
+pub struct Document {
+    text: String,
+    lines: LineCache,
+    encoding: PositionEncoding,
+}
+
+impl Document {
+    /// creates a document, whose positions are counted in `encoding`
+    pub fn new(text: impl Into<String>, encoding: PositionEncoding) -> Self {
+        let text = text.into();
+        Self {
+            lines: LineCache::with_terminators(&text, LineTerminators::LfCrCrLf),
+            text,
+            encoding,
+        }
+    }
+
+    #[inline(always)]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    #[inline(always)]
+    pub fn lines(&self) -> &LineCache {
+        &self.lines
+    }
+
+    #[inline(always)]
+    pub fn encoding(&self) -> PositionEncoding {
+        self.encoding
+    }
+
+    /// returns a converter between offsets and positions of the current text
+    #[inline]
+    pub fn converter(&self) -> Converter<'_> {
+        Converter::new(&self.text, &self.lines, self.encoding)
+    }
+
+    /// applies a single change; positions in its range are resolved like
+    /// with [`Converter::offset`], so they are clamped to the document.
+    pub fn apply_change(&mut self, change: &ContentChange) {
+        match change.range {
+            Some(range) => {
+                let range = self.converter().byte_range(range);
+                self.text.replace_range(range.clone(), &change.text);
+                self.lines.apply_edit(range, &self.text);
+            }
+            None => {
+                self.text.clear();
+                self.text.push_str(&change.text);
+                self.lines = LineCache::with_terminators(&self.text, self.lines.terminators());
+            }
+        }
+    }
+
+    /// applies the changes of a `didChange` notification, in order;
+    /// the range of each change refers to the text after the previous ones.
+    pub fn apply_changes<'c>(&mut self, changes: impl IntoIterator<Item = &'c ContentChange>) {
+        for change in changes {
+            self.apply_change(change);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Position;
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn range(l1: u32, c1: u32, l2: u32, c2: u32) -> Range {
+        Range::new(Position::new(l1, c1), Position::new(l2, c2))
+    }
+
+    /// asserts that the cache of `doc` matches a fresh one
+    fn assert_synced(doc: &Document) {
+        let fresh = LineCache::with_terminators(doc.text(), LineTerminators::LfCrCrLf);
+        let lines = doc.lines();
+        assert_eq!(lines.line_count(), fresh.line_count());
+        for l in 0..fresh.line_count() {
+            assert_eq!(lines.line_range(l), fresh.line_range(l));
+        }
+    }
+
+    #[test]
+    fn changes() {
+        let mut doc = Document::new(
+            "fn main() {\r\n    foo();\r\n}\r\n",
+            PositionEncoding::Utf16,
+        );
+        doc.apply_changes(&[
+            // rename `foo` to `bär😀`
+            ContentChange::new(range(1, 4, 1, 7), "bär😀"),
+            // refers to the positions after the first change
+            ContentChange::new(range(1, 10, 1, 10), "\n    baz()"),
+            // join the last two lines, `}` and the end are clamped
+            ContentChange::new(range(2, 9, 3, 100), ";}"),
+        ]);
+        assert_eq!(doc.text(), "fn main() {\r\n    bär😀(\n    baz();}\r\n");
+        assert_synced(&doc);
+        assert_eq!(
+            doc.converter().position(doc.text().len()),
+            Position::new(3, 0)
+        );
+
+        doc.apply_change(&ContentChange::full("a\rb"));
+        assert_eq!(doc.text(), "a\rb");
+        assert_synced(&doc);
+        assert_eq!(doc.lines().line_count(), 2);
+    }
+
+    #[test]
+    fn split_terminators() {
+        // inserting between `\r` and `\n` splits the terminator
+        let mut doc = Document::new("a\r\nb", PositionEncoding::Utf8);
+        let pos = doc.converter().position(2);
+        doc.apply_change(&ContentChange::new(Range::new(pos, pos), "x"));
+        // the position past the end of the first line is clamped before `\r`
+        assert_eq!(doc.text(), "ax\r\nb");
+        assert_synced(&doc);
+
+        let mut doc = Document::new("a\rb", PositionEncoding::Utf8);
+        doc.apply_change(&ContentChange::new(range(1, 0, 1, 0), "\n"));
+        assert_eq!(doc.text(), "a\r\nb");
+        assert_synced(&doc);
+        assert_eq!(doc.lines().line_count(), 2);
+
+        let texts: Vec<_> = (0..2).map(|l| doc.lines().line_range(l)).collect();
+        assert_eq!(texts, [Some(0..1), Some(3..4)]);
+    }
+}
@@ -0,0 +1,274 @@
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+//! Conversion between byte offsets and positions of the
+//! Language Server Protocol.
+//!
+//! LSP positions are zero-based (line, character) pairs, where characters
+//! are counted in the [`PositionEncoding`] negotiated between client and
+//! server. Lines are split according to the
+//! [`LineTerminators`](crate::LineTerminators) of the [`LineCache`], which
+//! should be [`LfCrCrLf`](crate::LineTerminators::LfCrCrLf) to match
+//! the protocol.
+//!
+//! ```
+//! use linetrack::{lsp::{Converter, Position, PositionEncoding}, LineCache, LineTerminators};
+//!
+//! let src = "a = \"😀\";\r\nb = 1;\r\n";
+//! let lines = LineCache::with_terminators(src, LineTerminators::LfCrCrLf);
+//! let encoding = PositionEncoding::negotiate(["utf-32", "utf-16"]);
+//! assert_eq!(encoding, PositionEncoding::Utf32);
+//!
+//! let conv = Converter::new(src, &lines, encoding);
+//! assert_eq!(conv.position(9), Position::new(0, 6));
+//! assert_eq!(conv.offset(Position::new(1, 4)), 17);
+//! // characters past the end of a line refer to its end
+//! assert_eq!(conv.offset(Position::new(0, 100)), 11);
+//! ```
+
+mod document;
+mod semantic_tokens;
+pub use document::{ContentChange, Document};
+pub use semantic_tokens::SemanticToken;
+
+use crate::{ColumnUnit, LineCache};
+use core::{fmt, ops};
+
+/// The unit in which the characters of LSP positions are counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PositionEncoding {
+    /// bytes of the UTF-8 encoding
+    Utf8,
+    /// code units of the UTF-16 encoding, which every server has to support
+    #[default]
+    Utf16,
+    /// unicode scalar values (`char`s)
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// parses the name used by the protocol, like `utf-16`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "utf-8" => Some(Self::Utf8),
+            "utf-16" => Some(Self::Utf16),
+            "utf-32" => Some(Self::Utf32),
+            _ => None,
+        }
+    }
+
+    /// returns the name used by the protocol
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Utf16 => "utf-16",
+            Self::Utf32 => "utf-32",
+        }
+    }
+
+    /// picks the encoding to use from the ones offered by the client
+    /// (`general.positionEncodings`, in order of preference), falling back
+    /// to UTF-16 if none of them is known.
+    pub fn negotiate<'n>(offered: impl IntoIterator<Item = &'n str>) -> Self {
+        offered
+            .into_iter()
+            .find_map(Self::from_name)
+            .unwrap_or_default()
+    }
+
+    /// returns the column unit counting in this encoding
+    #[inline]
+    pub fn unit(self) -> ColumnUnit {
+        match self {
+            Self::Utf8 => ColumnUnit::Byte,
+            Self::Utf16 => ColumnUnit::Utf16,
+            Self::Utf32 => ColumnUnit::Char,
+        }
+    }
+}
+
+impl fmt::Display for PositionEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A zero-based position in a text document.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub line: u32,
+    pub character: u32,
+}
+
+impl Position {
+    #[inline]
+    pub const fn new(line: u32, character: u32) -> Self {
+        Self { line, character }
+    }
+}
+
+/// A range in a text document, the end is exclusive.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Range {
+    #[inline]
+    pub const fn new(start: Position, end: Position) -> Self {
+        Self { start, end }
+    }
+}
+
+/// Converts between byte offsets of a source and LSP positions
+/// in a specific encoding.
+#[derive(Clone, Copy, Debug)]
+pub struct Converter<'a> {
+    src: &'a str,
+    lines: &'a LineCache,
+    encoding: PositionEncoding,
+}
+
+/// converts to `u32`, saturating
+#[inline]
+fn saturate(x: usize) -> u32 {
+    u32::try_from(x).unwrap_or(u32::MAX)
+}
+
+impl<'a> Converter<'a> {
+    /// `lines` has to be the line cache of `src`.
+    #[inline]
+    pub fn new(src: &'a str, lines: &'a LineCache, encoding: PositionEncoding) -> Self {
+        Self {
+            src,
+            lines,
+            encoding,
+        }
+    }
+
+    #[inline(always)]
+    pub fn encoding(&self) -> PositionEncoding {
+        self.encoding
+    }
+
+    /// returns the position of the byte offset `pos`, which has to lie on a
+    /// `char` boundary; offsets past the end are clamped to the end.
+    ///
+    /// Offsets inside a line terminator (like between `\r` and `\n`) result
+    /// in a character past the end of the line, which refers to the
+    /// end of the line when converted back.
+    pub fn position(&self, pos: usize) -> Position {
+        let pos = pos.min(self.src.len());
+        let (line, col) = self.lines.run_with(self.src, pos, self.encoding.unit());
+        Position::new(saturate(line), saturate(col))
+    }
+
+    /// returns the range of the byte range `range`
+    pub fn range(&self, range: ops::Range<usize>) -> Range {
+        Range::new(self.position(range.start), self.position(range.end))
+    }
+
+    /// returns the byte offset of `pos`.
+    ///
+    /// Following the protocol, a character past the end of the line refers
+    /// to the end of the line (before its terminator). A line past the end
+    /// of the document refers to the end of the document, and a character
+    /// in the middle of a `char` (e.g. between the halves of a surrogate
+    /// pair) is rounded down to the start of it.
+    pub fn offset(&self, pos: Position) -> usize {
+        let line = pos.line as usize;
+        let offset = self.lines.offset_clamped_with(
+            self.src,
+            line,
+            pos.character as usize,
+            self.encoding.unit(),
+        );
+        // the terminator isn't part of the line in the protocol
+        self.lines
+            .line_range(line)
+            .map_or(offset, |r| offset.min(r.end))
+    }
+
+    /// returns the byte range of `range`, like [`offset`](Self::offset);
+    /// an end before the start is moved to the start.
+    pub fn byte_range(&self, range: Range) -> ops::Range<usize> {
+        let start = self.offset(range.start);
+        start..self.offset(range.end).max(start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LineTerminators;
+
+    #[test]
+    fn encodings() {
+        assert_eq!(PositionEncoding::default(), PositionEncoding::Utf16);
+        for enc in [
+            PositionEncoding::Utf8,
+            PositionEncoding::Utf16,
+            PositionEncoding::Utf32,
+        ] {
+            assert_eq!(PositionEncoding::from_name(enc.name()), Some(enc));
+        }
+        assert_eq!(PositionEncoding::from_name("ucs-2"), None);
+        assert_eq!(
+            PositionEncoding::negotiate(["utf-7", "utf-8", "utf-16"]),
+            PositionEncoding::Utf8
+        );
+        assert_eq!(
+            PositionEncoding::negotiate(["utf-7"]),
+            PositionEncoding::Utf16
+        );
+        assert_eq!(PositionEncoding::negotiate([]), PositionEncoding::Utf16);
+    }
+
+    #[test]
+    fn conversion() {
+        // "ä" takes 2 bytes, "😀" 4 bytes or 2 UTF-16 code units
+        let src = "ä😀x\r\n\ty\n";
+        let lines = LineCache::with_terminators(src, LineTerminators::LfCrCrLf);
+        let x = src.find('x').unwrap();
+        for (enc, col) in [
+            (PositionEncoding::Utf8, 6),
+            (PositionEncoding::Utf16, 3),
+            (PositionEncoding::Utf32, 2),
+        ] {
+            let conv = Converter::new(src, &lines, enc);
+            assert_eq!(conv.position(x), Position::new(0, col));
+            assert_eq!(conv.offset(Position::new(0, col)), x);
+            assert_eq!(
+                conv.range(x..x + 4),
+                Range::new(Position::new(0, col), Position::new(1, 1))
+            );
+            assert_eq!(conv.byte_range(conv.range(x..x + 4)), x..x + 4);
+
+            // past the end of a line, or of the document
+            assert_eq!(conv.offset(Position::new(0, 1000)), x + 1);
+            // between `\r` and `\n`
+            assert_eq!(conv.offset(Position::new(0, col + 2)), x + 1);
+            assert_eq!(conv.offset(Position::new(1, 3)), src.len() - 1);
+            assert_eq!(conv.offset(Position::new(2, 0)), src.len());
+            assert_eq!(conv.offset(Position::new(9, 9)), src.len());
+            assert_eq!(conv.position(1000), Position::new(2, 0));
+
+            // every char boundary round-trips
+            for (pos, _) in src.char_indices().filter(|&(_, c)| c != '\n') {
+                assert_eq!(conv.offset(conv.position(pos)), pos, "{:?} {}", enc, pos);
+            }
+        }
+
+        // in the middle of a surrogate pair, or of a UTF-8 sequence
+        let conv = Converter::new(src, &lines, PositionEncoding::Utf16);
+        assert_eq!(conv.offset(Position::new(0, 2)), 2);
+        let conv = Converter::new(src, &lines, PositionEncoding::Utf8);
+        assert_eq!(conv.offset(Position::new(0, 1)), 0);
+
+        // reversed ranges
+        assert_eq!(
+            conv.byte_range(Range::new(Position::new(1, 0), Position::new(0, 0))),
+            x + 3..x + 3
+        );
+    }
+}
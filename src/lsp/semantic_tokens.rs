@@ -0,0 +1,219 @@
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+use super::{saturate, Converter, Position};
+use alloc::vec::Vec;
+use core::ops;
+
+/// A semantic token, covering a byte range of the source.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SemanticToken {
+    pub range: ops::Range<usize>,
+    /// the index into the token types of the legend
+    pub token_type: u32,
+    /// a bit set of indices into the token modifiers of the legend
+    pub modifiers: u32,
+}
+
+impl Converter<'_> {
+    /// encodes `tokens` into the `data` of a `SemanticTokens` response:
+    /// five integers per token, whose position is relative to the
+    /// previous one.
+    ///
+    /// If the client doesn't support `multilineTokens`, tokens spanning
+    /// several lines are split into one token per line, otherwise their
+    /// length includes the line terminators. Empty tokens (or pieces of
+    /// them) are skipped.
+    ///
+    /// # Panics
+    /// Panics if the tokens aren't sorted, overlap, or reach past the end
+    /// of the source.
+    pub fn encode_tokens(&self, tokens: &[SemanticToken], multiline: bool) -> Vec<u32> {
+        let mut data = Vec::with_capacity(tokens.len() * 5);
+        let mut prev = Position::default();
+        let mut prev_end = 0;
+        for token in tokens {
+            let range = token.range.clone();
+            assert!(
+                prev_end <= range.start && range.start <= range.end && range.end <= self.src.len(),
+                "semantic tokens have to be sorted, must not overlap and must lie within the source"
+            );
+            prev_end = range.end;
+            if multiline {
+                self.push_token(&mut data, &mut prev, range, token);
+                continue;
+            }
+            let (first, _) = self.lines.run(range.start);
+            let (last, _) = self.lines.run(range.end);
+            for line in first..=last {
+                let Some(bounds) = self.lines.line_range(line) else {
+                    break;
+                };
+                let piece = range.start.max(bounds.start)..range.end.min(bounds.end);
+                self.push_token(&mut data, &mut prev, piece, token);
+            }
+        }
+        data
+    }
+
+    fn push_token(
+        &self,
+        data: &mut Vec<u32>,
+        prev: &mut Position,
+        range: ops::Range<usize>,
+        token: &SemanticToken,
+    ) {
+        if range.is_empty() {
+            return;
+        }
+        let pos = self.position(range.start);
+        let delta_start = if pos.line == prev.line {
+            pos.character - prev.character
+        } else {
+            pos.character
+        };
+        let len = self.encoding.unit().count(self.src[range].as_bytes());
+        data.extend([
+            pos.line - prev.line,
+            delta_start,
+            saturate(len),
+            token.token_type,
+            token.modifiers,
+        ]);
+        *prev = pos;
+    }
+
+    /// decodes the `data` of a `SemanticTokens` response,
+    /// the inverse of [`encode_tokens`](Self::encode_tokens).
+    ///
+    /// Returns `None` if `data` is malformed, or if a token doesn't fit
+    /// into the source or starts or ends in the middle of a `char`.
+    pub fn decode_tokens(&self, data: &[u32]) -> Option<Vec<SemanticToken>> {
+        if data.len() % 5 != 0 {
+            return None;
+        }
+        let unit = self.encoding.unit();
+        let mut tokens = Vec::with_capacity(data.len() / 5);
+        let mut pos = Position::default();
+        for chunk in data.chunks_exact(5) {
+            let (delta_line, delta_start, len) = (chunk[0], chunk[1], chunk[2]);
+            pos = if delta_line == 0 {
+                Position::new(pos.line, pos.character.checked_add(delta_start)?)
+            } else {
+                Position::new(pos.line.checked_add(delta_line)?, delta_start)
+            };
+            let start = self.lines.offset_with(
+                self.src,
+                pos.line as usize,
+                pos.character as usize,
+                unit,
+            )?;
+            let (mut end, mut acc) = (start, 0);
+            for (bytes, cols) in unit.segments(&self.src[start..]) {
+                if acc >= len as usize {
+                    break;
+                }
+                acc += cols;
+                end += bytes;
+            }
+            if acc != len as usize {
+                return None;
+            }
+            tokens.push(SemanticToken {
+                range: start..end,
+                token_type: chunk[3],
+                modifiers: chunk[4],
+            });
+        }
+        Some(tokens)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::PositionEncoding;
+    use super::*;
+    use crate::{LineCache, LineTerminators};
+
+    fn token(range: ops::Range<usize>, token_type: u32) -> SemanticToken {
+        SemanticToken {
+            range,
+            token_type,
+            modifiers: 0,
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let src = "fn größe() {}\r\n/* a\r\n😀 */ x\n";
+        let lines = LineCache::with_terminators(src, LineTerminators::LfCrCrLf);
+        let comment = src.find("/*").unwrap();
+        let tokens = [
+            token(0..2, 0),
+            token(3..10, 1),
+            token(comment..src.find(" x").unwrap(), 2),
+            token(src.len() - 2..src.len() - 1, 3),
+        ];
+
+        let conv = Converter::new(src, &lines, PositionEncoding::Utf16);
+        let data = conv.encode_tokens(&tokens, true);
+        assert_eq!(
+            data,
+            [0, 0, 2, 0, 0, 0, 3, 5, 1, 0, 1, 0, 11, 2, 0, 1, 6, 1, 3, 0]
+        );
+        assert_eq!(conv.decode_tokens(&data).unwrap(), tokens);
+
+        // split into lines, the terminators aren't part of any token
+        let data = conv.encode_tokens(&tokens, false);
+        assert_eq!(
+            data,
+            [0, 0, 2, 0, 0, 0, 3, 5, 1, 0, 1, 0, 4, 2, 0, 1, 0, 5, 2, 0, 0, 6, 1, 3, 0]
+        );
+        let decoded = conv.decode_tokens(&data).unwrap();
+        assert_eq!(decoded[2].range, comment..comment + 4);
+        assert_eq!(decoded[3].range, comment + 6..src.len() - 3);
+
+        // other encodings only change the lengths and start characters
+        let conv = Converter::new(src, &lines, PositionEncoding::Utf8);
+        let data = conv.encode_tokens(&tokens, false);
+        assert_eq!(data[5..10], [0, 3, 7, 1, 0]);
+        assert_eq!(data[15..20], [1, 0, 7, 2, 0]);
+        assert_eq!(
+            conv.decode_tokens(&conv.encode_tokens(&tokens, true))
+                .unwrap(),
+            tokens
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let src = "a😀\nb\n";
+        let lines = LineCache::new(src);
+        let conv = Converter::new(src, &lines, PositionEncoding::Utf16);
+        assert_eq!(conv.decode_tokens(&[0, 0, 1, 0]), None);
+        // past the end of the source
+        assert_eq!(conv.decode_tokens(&[0, 0, 100, 0, 0]), None);
+        assert_eq!(conv.decode_tokens(&[5, 0, 1, 0, 0]), None);
+        // into the middle of a surrogate pair
+        assert_eq!(conv.decode_tokens(&[0, 0, 2, 0, 0]), None);
+        assert_eq!(conv.decode_tokens(&[0, 2, 1, 0, 0]), None);
+        assert_eq!(conv.decode_tokens(&[]), Some(Vec::new()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn unsorted() {
+        let src = "a b";
+        let lines = LineCache::new(src);
+        let conv = Converter::new(src, &lines, PositionEncoding::Utf16);
+        conv.encode_tokens(&[token(2..3, 0), token(0..1, 0)], false);
+    }
+
+    #[test]
+    #[should_panic]
+    fn past_the_end() {
+        let src = "ab";
+        let lines = LineCache::new(src);
+        let conv = Converter::new(src, &lines, PositionEncoding::Utf16);
+        conv.encode_tokens(&[token(0..10, 0)], true);
+    }
+}
@@ -0,0 +1,274 @@
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+use crate::{ColumnUnit, LineCache, PosTrackerExtern, PosTrackerStream};
+use core::{fmt, ops::Range};
+
+/// Whether lines and columns are displayed starting from 0 or 1.
+///
+/// Positions are always stored zero-based, this only affects formatting.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum IndexBase {
+    Zero,
+    /// the usual convention of compilers and editors
+    #[default]
+    One,
+}
+
+impl IndexBase {
+    #[inline]
+    fn shift(self, x: usize) -> usize {
+        match self {
+            IndexBase::Zero => x,
+            IndexBase::One => x + 1,
+        }
+    }
+}
+
+/// A position in a source, with zero-based line and column.
+///
+/// Positions are ordered by their offset first,
+/// which is consistent with their line and column
+/// as long as they belong to the same source.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    #[inline]
+    pub fn new(offset: usize, line: usize, column: usize) -> Self {
+        Self {
+            offset,
+            line,
+            column,
+        }
+    }
+
+    /// returns a formatter printing `line:column` in the given `base`
+    #[inline]
+    pub fn display(&self, base: IndexBase) -> impl fmt::Display + '_ {
+        DisplayPosition(self, base)
+    }
+}
+
+struct DisplayPosition<'a>(&'a Position, IndexBase);
+
+impl fmt::Display for DisplayPosition<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let DisplayPosition(pos, base) = *self;
+        write!(f, "{}:{}", base.shift(pos.line), base.shift(pos.column))
+    }
+}
+
+/// prints `line:column`, one-based
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display(IndexBase::One).fmt(f)
+    }
+}
+
+/// A half-open span between two positions in a source.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    /// # Panics
+    /// Panics if `end` lies before `start`.
+    #[inline]
+    pub fn new(start: Position, end: Position) -> Self {
+        assert!(start.offset <= end.offset, "span ends before it starts");
+        Self { start, end }
+    }
+
+    /// returns the byte range covered by this span
+    #[inline]
+    pub fn range(&self) -> Range<usize> {
+        self.start.offset..self.end.offset
+    }
+
+    /// returns the length in bytes
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.end.offset - self.start.offset
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.start.offset == self.end.offset
+    }
+
+    /// returns `true` if the span covers more than a single line
+    #[inline]
+    pub fn is_multiline(&self) -> bool {
+        self.start.line != self.end.line
+    }
+
+    /// returns `true` if `pos` lies in this span; an empty span
+    /// contains its own start
+    #[inline]
+    pub fn contains(&self, pos: Position) -> bool {
+        pos.offset == self.start.offset || self.range().contains(&pos.offset)
+    }
+
+    /// returns `true` if `other` lies completely in this span
+    #[inline]
+    pub fn contains_span(&self, other: &Span) -> bool {
+        self.start.offset <= other.start.offset && other.end.offset <= self.end.offset
+    }
+
+    /// returns `true` if both spans share at least one byte;
+    /// an empty span overlaps a span which contains its start
+    #[inline]
+    pub fn overlaps(&self, other: &Span) -> bool {
+        match (self.is_empty(), other.is_empty()) {
+            (true, _) => other.contains(self.start),
+            (_, true) => self.contains(other.start),
+            _ => self.start.offset < other.end.offset && other.start.offset < self.end.offset,
+        }
+    }
+
+    /// returns the smallest span covering both spans
+    #[inline]
+    pub fn merge(&self, other: &Span) -> Span {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+
+    /// returns the span covered by both spans, if they overlap
+    pub fn intersect(&self, other: &Span) -> Option<Span> {
+        if !self.overlaps(other) {
+            return None;
+        }
+        Some(Span {
+            start: self.start.max(other.start),
+            end: self.end.min(other.end),
+        })
+    }
+
+    /// returns a formatter printing `line:column-line:column` in the given `base`
+    #[inline]
+    pub fn display(&self, base: IndexBase) -> impl fmt::Display + '_ {
+        DisplaySpan(self, base)
+    }
+}
+
+struct DisplaySpan<'a>(&'a Span, IndexBase);
+
+impl fmt::Display for DisplaySpan<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let DisplaySpan(span, base) = *self;
+        write!(f, "{}-{}", span.start.display(base), span.end.display(base))
+    }
+}
+
+/// prints `line:column-line:column`, one-based
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display(IndexBase::One).fmt(f)
+    }
+}
+
+impl LineCache {
+    /// like [`run`](Self::run), but returns a [`Position`]
+    #[inline]
+    pub fn position(&self, pos: usize) -> Position {
+        let (line, column) = self.run(pos);
+        Position::new(pos, line, column)
+    }
+
+    /// like [`run_with`](Self::run_with), but returns a [`Position`]
+    #[inline]
+    pub fn position_with(&self, src: &str, pos: usize, unit: ColumnUnit) -> Position {
+        let (line, column) = self.run_with(src, pos, unit);
+        Position::new(pos, line, column)
+    }
+
+    /// converts a byte range into a [`Span`], with columns counted in bytes
+    ///
+    /// # Panics
+    /// Panics if `range` ends before it starts.
+    #[inline]
+    pub fn span(&self, range: Range<usize>) -> Span {
+        Span::new(self.position(range.start), self.position(range.end))
+    }
+
+    /// like [`span`](Self::span), but counts columns in the given `unit`
+    #[inline]
+    pub fn span_with(&self, src: &str, range: Range<usize>, unit: ColumnUnit) -> Span {
+        Span::new(
+            self.position_with(src, range.start, unit),
+            self.position_with(src, range.end, unit),
+        )
+    }
+}
+
+impl PosTrackerExtern {
+    /// returns the current position
+    #[inline]
+    pub fn position(&self) -> Position {
+        Position::new(self.offset(), self.line(), self.column())
+    }
+}
+
+impl PosTrackerStream {
+    /// returns the current position
+    #[inline]
+    pub fn position(&self) -> Position {
+        Position::new(self.offset(), self.line(), self.column())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    #[test]
+    fn spans() {
+        const SRC: &str = "fn main() {\n    ä();\n}\n";
+        let lc = LineCache::new(SRC);
+        let body = lc.span(10..22);
+        assert!(body.is_multiline());
+        assert_eq!(format!("{}", body), "1:11-3:1");
+        assert_eq!(format!("{}", body.display(IndexBase::Zero)), "0:10-2:0");
+
+        let call = lc.span_with(SRC, 16..20, ColumnUnit::Char);
+        assert_eq!(call.start, Position::new(16, 1, 4));
+        assert_eq!(call.end, Position::new(20, 1, 7));
+        assert_eq!(format!("{}", call.end), "2:8");
+        assert!(body.contains_span(&call));
+        assert!(body.overlaps(&call));
+        assert_eq!(call.len(), 4);
+
+        let name = lc.span(3..7);
+        assert!(!name.overlaps(&body));
+        assert_eq!(name.intersect(&body), None);
+        assert_eq!(name.merge(&call).range(), 3..20);
+        assert_eq!(
+            body.intersect(&lc.span(0..12)).map(|s| s.range()),
+            Some(10..12)
+        );
+
+        let empty = lc.span(10..10);
+        assert!(empty.is_empty());
+        assert!(body.contains(empty.start));
+        assert!(!body.contains(body.end));
+        assert!(empty.overlaps(&body) && body.overlaps(&empty));
+        assert!(name < body);
+    }
+
+    #[test]
+    fn tracker_position() {
+        const SRC: &[u8] = b"ab\ncd";
+        let mut pt = PosTrackerExtern::default();
+        pt.update(SRC, 4);
+        assert_eq!(pt.position(), Position::new(4, 1, 1));
+    }
+}
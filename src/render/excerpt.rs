@@ -0,0 +1,235 @@
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+use super::DISPLAY_UNIT;
+use crate::LineCache;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// the marker inserted where a line got cropped
+const ELLIPSIS: &str = "...";
+
+/// A single source line, cropped to fit a width.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Excerpt {
+    /// the (zero-based) line the excerpt was taken from
+    pub line: usize,
+    /// the visible part of the line, with tabs expanded and `...` in place
+    /// of the cropped parts
+    pub text: String,
+    /// the display columns of the highlighted span in `text`
+    pub highlight: Range<usize>,
+}
+
+impl Excerpt {
+    /// returns a line placing `marker` below the highlighted span,
+    /// at least once even if the span is empty
+    pub fn underline(&self, marker: char) -> String {
+        let len = self.highlight.len().max(1);
+        let mut s = String::with_capacity(self.highlight.start + len);
+        s.extend(core::iter::repeat_n(' ', self.highlight.start));
+        s.extend(core::iter::repeat_n(marker, len));
+        s
+    }
+}
+
+/// Crops the line containing the start of `span` to at most `width` display
+/// columns, keeping the highlighted span (as far as it is part of that line)
+/// in view and centering it if there is room around it.
+///
+/// Cropped parts are replaced by `...`, unless there is no room for the
+/// markers next to the first highlighted char; the text is never wider than
+/// `width`, and chars which only partly fit are left out.
+///
+/// Tabs are expanded to multiples of `tab_width`; with the `unicode-width`
+/// feature, columns are measured in terminal cells, otherwise in `char`s.
+/// `lines` has to be the line cache of `src` and `span` has to lie on `char`
+/// boundaries.
+pub fn excerpt(
+    src: &str,
+    lines: &LineCache,
+    span: Range<usize>,
+    width: usize,
+    tab_width: usize,
+) -> Excerpt {
+    let (line, _) = lines.run(span.start);
+    let range = lines.line_range(line).unwrap_or(0..0);
+
+    // every char of the line: (byte offset, display column, display width)
+    let mut chars = Vec::new();
+    let mut col = 0;
+    for (i, c) in src[range.clone()].char_indices() {
+        let w = if c == '\t' {
+            crate::next_tab_stop(col, tab_width) - col
+        } else {
+            DISPLAY_UNIT.char_width(c)
+        };
+        chars.push((range.start + i, col, w));
+        col += w;
+    }
+    let total = col;
+    let col_of = |pos: usize| {
+        chars
+            .iter()
+            .find(|&&(i, _, _)| i >= pos)
+            .map_or(total, |&(_, c, _)| c)
+    };
+    let hl_start = col_of(span.start);
+    let hl_end = col_of(span.end.min(range.end)).max(hl_start);
+    // the columns needed to show at least the first highlighted char
+    let need = chars
+        .iter()
+        .find(|&&(i, _, _)| i >= span.start)
+        .map_or(1, |&(_, _, w)| w.max(1));
+
+    // the visible window of display columns
+    let (mut from, mut to) = (0, total);
+    let mut marker = 0;
+    if total > width {
+        if width >= 2 * ELLIPSIS.len() + need {
+            marker = ELLIPSIS.len();
+        }
+        let avail = width - 2 * marker;
+        from = if hl_end - hl_start >= avail {
+            hl_start
+        } else {
+            hl_start.saturating_sub((avail - (hl_end - hl_start)) / 2)
+        };
+        from = from.min(total - avail);
+        to = from + avail;
+        // there is no marker needed on a side which isn't cropped,
+        // give its room to the other side
+        if from <= marker {
+            from = 0;
+            to = width.saturating_sub(marker).max(to);
+        } else if to + marker >= total {
+            to = total;
+            from = total.saturating_sub(width.saturating_sub(marker)).min(from);
+        }
+    }
+
+    let mut text = String::new();
+    if from > 0 {
+        text.push_str(&ELLIPSIS[..marker]);
+    }
+    // the display column in `text` of the first visible char
+    let shift = text.len();
+    let mut first = None;
+    let mut last = 0;
+    for &(i, c, w) in &chars {
+        if c < from || c + w > to {
+            continue;
+        }
+        let base = *first.get_or_insert(c);
+        let ch = src[i..].chars().next().unwrap_or(' ');
+        if ch == '\t' {
+            text.extend(core::iter::repeat_n(' ', w));
+        } else {
+            text.push(ch);
+        }
+        last = c + w - base;
+    }
+    let base = first.unwrap_or(from);
+    let end = shift + last.min(to - base);
+    if to < total {
+        text.push_str(&ELLIPSIS[..marker]);
+    }
+
+    let clip = |c: usize| (shift + c.saturating_sub(base)).min(end);
+    Excerpt {
+        line,
+        text,
+        highlight: clip(hl_start)..clip(hl_end),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    fn crop(src: &str, span: Range<usize>, width: usize) -> (String, String) {
+        let lines = LineCache::new(src);
+        let e = excerpt(src, &lines, span, width, 4);
+        (e.text.clone(), e.underline('^'))
+    }
+
+    #[test]
+    fn short_lines() {
+        let (text, carets) = crop("a\nlet x = 1;\n", 6..7, 80);
+        assert_eq!(text, "let x = 1;");
+        assert_eq!(carets, "    ^");
+
+        let (text, carets) = crop("\tx\n", 1..2, 80);
+        assert_eq!(text, "    x");
+        assert_eq!(carets, "    ^");
+    }
+
+    #[test]
+    fn long_lines() {
+        let src: String = (0..100).map(|i| (b'a' + i % 26) as char).collect();
+
+        // cropped on both sides, the highlight is centered
+        let (text, carets) = crop(&src, 50..52, 20);
+        assert_eq!(text, "...stuvwxyzabcdef...");
+        assert_eq!(carets, "         ^^");
+        assert_eq!(&text[9..11], &src[50..52]);
+
+        // near the start, only the end is cropped
+        let (text, carets) = crop(&src, 2..3, 20);
+        assert_eq!(text, "abcdefghijklmnopq...");
+        assert_eq!(carets, "  ^");
+
+        // near the end, only the start is cropped
+        let (text, carets) = crop(&src, 98..100, 20);
+        assert_eq!(text, "...fghijklmnopqrstuv");
+        assert_eq!(carets, "                  ^^");
+
+        // highlights wider than the window are cut off
+        let (text, carets) = crop(&src, 30..90, 20);
+        assert_eq!(text, "...efghijklmnopqr...");
+        assert_eq!(carets, "   ^^^^^^^^^^^^^^");
+
+        // a span reaching into the next line only highlights its first line
+        let src = format!("{}\nnext\n", src);
+        let (text, carets) = crop(&src, 95..103, 20);
+        assert_eq!(text, "...fghijklmnopqrstuv");
+        assert_eq!(carets, "               ^^^^^");
+
+        // too narrow for the markers
+        let (text, carets) = crop(&src, 50..52, 5);
+        assert_eq!(text, "xyzab");
+        assert_eq!(carets, " ^^");
+        let (text, carets) = crop(&src, 2..3, 1);
+        assert_eq!(text, "c");
+        assert_eq!(carets, "^");
+        let (text, _) = crop(&src, 2..3, 0);
+        assert_eq!(text, "");
+        for width in 0..30 {
+            for span in [0..1, 50..60, 99..100] {
+                assert!(crop(&src, span, width).0.len() <= width);
+            }
+        }
+    }
+
+    #[cfg(feature = "unicode-width")]
+    #[test]
+    fn wide_characters() {
+        let src: String = core::iter::repeat_n('漢', 40).collect();
+        let (text, carets) = crop(&src, 60..63, 20);
+        // wide chars only appear completely
+        assert_eq!(text, "...漢漢漢漢漢漢漢...");
+        assert_eq!(carets, "         ^^");
+
+        // the markers only fit around a single wide char from width 8 on
+        let (text, carets) = crop(&src, 60..63, 8);
+        assert_eq!(text, "...漢...");
+        assert_eq!(carets, "   ^^");
+        let (text, carets) = crop(&src, 60..63, 7);
+        assert_eq!(text, "漢漢漢");
+        assert_eq!(carets, "  ^^");
+        let (text, carets) = crop(&src, 60..63, 1);
+        assert_eq!(text, "");
+        assert_eq!(carets, "^");
+    }
+}
@@ -0,0 +1,397 @@
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+use super::{digits, Cell, Kind, Level, Output, Renderer, Row, DISPLAY_UNIT};
+use crate::{LineCache, LineTerminators};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use core::ops::Range;
+
+/// A replacement of a byte span of the source.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Suggestion {
+    /// an empty range inserts the replacement
+    pub range: Range<usize>,
+    /// an empty replacement deletes the range
+    pub replacement: String,
+}
+
+/// The error returned when a suggestion can't be added to a [`Fix`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidSuggestion {
+    /// the range ends before it starts
+    Reversed(Range<usize>),
+    /// the range reaches past the end of the source
+    OutOfBounds(Range<usize>),
+    /// the range doesn't lie on `char` boundaries of the source
+    NotCharBoundary(Range<usize>),
+    /// the suggestion overlaps one which was added before
+    Overlap {
+        existing: Range<usize>,
+        new: Range<usize>,
+    },
+}
+
+impl fmt::Display for InvalidSuggestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reversed(r) => write!(f, "suggestion for {}..{} is reversed", r.start, r.end),
+            Self::OutOfBounds(r) => write!(
+                f,
+                "suggestion for {}..{} reaches past the end of the source",
+                r.start, r.end
+            ),
+            Self::NotCharBoundary(r) => write!(
+                f,
+                "suggestion for {}..{} doesn't lie on char boundaries",
+                r.start, r.end
+            ),
+            Self::Overlap { existing, new } => write!(
+                f,
+                "suggestion for {}..{} overlaps the one for {}..{}",
+                new.start, new.end, existing.start, existing.end
+            ),
+        }
+    }
+}
+
+impl core::error::Error for InvalidSuggestion {}
+
+/// How the renderer shows a [`Fix`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DiffStyle {
+    /// the affected lines, first as they are (`-`) and then as they would be
+    /// after applying the suggestions (`+`)
+    #[default]
+    Lines,
+    /// the fixed line, with the replaced parts underlined with `~`
+    /// and insertions with `+`. Suggestions spanning lines or deleting text
+    /// are still shown like with [`Lines`](Self::Lines).
+    Inline,
+}
+
+/// A set of non-overlapping suggestions for a source.
+#[derive(Clone, Debug)]
+pub struct Fix<'a> {
+    src: &'a str,
+    lines: &'a LineCache,
+    /// sorted by their ranges
+    suggestions: Vec<Suggestion>,
+}
+
+impl<'a> Fix<'a> {
+    /// `lines` has to be the line cache of `src`.
+    #[inline]
+    pub fn new(src: &'a str, lines: &'a LineCache) -> Self {
+        Self {
+            src,
+            lines,
+            suggestions: Vec::new(),
+        }
+    }
+
+    /// adds a suggestion; `range` has to lie on `char` boundaries of the
+    /// source. Suggestions may touch each other, but must not overlap,
+    /// which includes two insertions at the same position.
+    pub fn add(
+        &mut self,
+        range: Range<usize>,
+        replacement: impl Into<String>,
+    ) -> Result<(), InvalidSuggestion> {
+        if range.start > range.end {
+            return Err(InvalidSuggestion::Reversed(range));
+        }
+        if range.end > self.src.len() {
+            return Err(InvalidSuggestion::OutOfBounds(range));
+        }
+        if !self.src.is_char_boundary(range.start) || !self.src.is_char_boundary(range.end) {
+            return Err(InvalidSuggestion::NotCharBoundary(range));
+        }
+        if let Some(s) = self.suggestions.iter().find(|s| overlaps(&s.range, &range)) {
+            return Err(InvalidSuggestion::Overlap {
+                existing: s.range.clone(),
+                new: range,
+            });
+        }
+        let idx = self
+            .suggestions
+            .partition_point(|s| (s.range.start, s.range.end) < (range.start, range.end));
+        self.suggestions.insert(
+            idx,
+            Suggestion {
+                range,
+                replacement: replacement.into(),
+            },
+        );
+        Ok(())
+    }
+
+    /// returns the suggestions, ordered by their position
+    #[inline(always)]
+    pub fn suggestions(&self) -> &[Suggestion] {
+        &self.suggestions
+    }
+
+    /// returns the source with all suggestions applied
+    pub fn apply(&self) -> String {
+        self.apply_range(0..self.src.len(), &self.suggestions).0
+    }
+
+    /// applies `suggestions`, which have to lie inside of `range`, to that
+    /// part of the source, and returns it together with the ranges of the
+    /// replacements in it
+    fn apply_range(
+        &self,
+        range: Range<usize>,
+        suggestions: &[Suggestion],
+    ) -> (String, Vec<Range<usize>>) {
+        let mut out = String::with_capacity(range.len());
+        let mut replaced = Vec::with_capacity(suggestions.len());
+        let mut pos = range.start;
+        for s in suggestions {
+            out.push_str(&self.src[pos..s.range.start]);
+            replaced.push(out.len()..out.len() + s.replacement.len());
+            out.push_str(&s.replacement);
+            pos = s.range.end;
+        }
+        out.push_str(&self.src[pos..range.end]);
+        (out, replaced)
+    }
+
+    /// groups the suggestions into the lines they touch,
+    /// returns (lines, suggestions) for each group
+    fn hunks(&self) -> Vec<(Range<usize>, Range<usize>)> {
+        let mut hunks: Vec<(Range<usize>, Range<usize>)> = Vec::new();
+        for (i, s) in self.suggestions.iter().enumerate() {
+            let (first, _) = self.lines.run(s.range.start);
+            let (mut last, col) = self.lines.run(s.range.end);
+            if col == 0 && last > first {
+                // the range ends with a line terminator
+                last -= 1;
+            }
+            match hunks.last_mut() {
+                // merge hunks which overlap or are adjacent
+                Some((lines, idxs)) if first <= lines.end => {
+                    lines.end = lines.end.max(last + 1);
+                    idxs.end = i + 1;
+                }
+                _ => hunks.push((first..last + 1, i..i + 1)),
+            }
+        }
+        hunks
+    }
+
+    /// returns the byte range of `lines`, including the last terminator
+    fn lines_range(&self, lines: Range<usize>) -> Range<usize> {
+        let start = self.lines.line_start(lines.start).unwrap_or(0);
+        let end = self.lines.line_start(lines.end).unwrap_or(self.src.len());
+        start..end
+    }
+}
+
+#[inline]
+fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
+    (a.start < b.end && b.start < a.end) || (a.is_empty() && a == b)
+}
+
+/// splits `text` into lines, without terminators; a final line is only
+/// returned if it isn't empty
+fn split_lines(text: &str, terminators: LineTerminators) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut prev = 0;
+    for (start, end) in terminators.find_iter(text.as_bytes()) {
+        lines.push(&text[prev..start]);
+        prev = end;
+    }
+    if prev < text.len() {
+        lines.push(&text[prev..]);
+    }
+    lines
+}
+
+impl Renderer {
+    /// renders the suggestions of `fix` as a diff
+    pub fn render_fix(&self, fix: &Fix<'_>) -> String {
+        let mut out = String::new();
+        // writing into a `String` can't fail
+        let _ = self.render_fix_to(fix, &mut out);
+        out
+    }
+
+    /// renders the suggestions of `fix` as a diff into `out`
+    pub fn render_fix_to(&self, fix: &Fix<'_>, out: &mut dyn Write) -> fmt::Result {
+        let terminators = fix.lines.terminators();
+        // (old lines, their text, first new line, new text, suggestions, replacements)
+        let mut hunks = Vec::new();
+        let mut delta = 0isize;
+        for (lines, idxs) in fix.hunks() {
+            let range = fix.lines_range(lines.clone());
+            let old = split_lines(&fix.src[range.clone()], terminators);
+            let (new, replaced) = fix.apply_range(range, &fix.suggestions[idxs.clone()]);
+            let new_first = lines.start.saturating_add_signed(delta);
+            delta += split_lines(&new, terminators).len() as isize - old.len() as isize;
+            hunks.push((lines, old, new_first, new, idxs, replaced));
+        }
+        let last_line = hunks
+            .iter()
+            .map(|h| h.0.end.max(h.2 + split_lines(&h.3, terminators).len()))
+            .max();
+        let mut out = Output {
+            out,
+            theme: &self.theme,
+            level: Level::Help,
+            gutter: digits(last_line.unwrap_or(1)),
+        };
+        if hunks.is_empty() {
+            return Ok(());
+        }
+        out.row(None, '|', &[])?;
+
+        let mut prev = None;
+        for (lines, old, new_first, new, idxs, replaced) in &hunks {
+            if prev.is_some_and(|p| p < lines.start) {
+                out.line(&super::cells("...", Kind::Gutter))?;
+            }
+            prev = Some(lines.end);
+            let new_lines = split_lines(new, terminators);
+            let suggestions = &fix.suggestions[idxs.clone()];
+            let inline = self.diff_style == DiffStyle::Inline
+                && old.len() == 1
+                && new_lines.len() == 1
+                && suggestions.iter().all(|s| !s.replacement.is_empty());
+
+            if inline {
+                let text = new_lines[0];
+                let mut row = Row::new(&[]);
+                row.push_message(0, &self.expand_tabs(text), Kind::Source);
+                out.row(Some(*new_first), '|', &row.cells)?;
+                let col = |b: usize| DISPLAY_UNIT.advance(&text.as_bytes()[..b], 0, self.tab_width);
+                let mut row = Row::new(&[]);
+                for (s, r) in suggestions.iter().zip(replaced) {
+                    let marker: Cell = if s.range.is_empty() {
+                        ('+', Kind::Addition)
+                    } else {
+                        ('~', Kind::Addition)
+                    };
+                    for c in col(r.start)..col(r.end) {
+                        row.put(c, marker);
+                    }
+                }
+                out.row(None, '|', &row.cells)?;
+            } else {
+                for (i, text) in old.iter().enumerate() {
+                    let mut row = Row::new(&[]);
+                    row.push_message(0, &self.expand_tabs(text), Kind::Removal);
+                    out.row(Some(lines.start + i), '-', &row.cells)?;
+                }
+                for (i, text) in new_lines.iter().enumerate() {
+                    let mut row = Row::new(&[]);
+                    row.push_message(0, &self.expand_tabs(text), Kind::Addition);
+                    out.row(Some(new_first + i), '+', &row.cells)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply() {
+        let src = "let a = foo(1);\nlet b = foo(2);\n";
+        let lines = LineCache::new(src);
+        let mut fix = Fix::new(src, &lines);
+        fix.add(24..27, "bar").unwrap();
+        fix.add(8..11, "bar").unwrap();
+        fix.add(0..0, "// fixed\n").unwrap();
+        fix.add(12..13, "").unwrap();
+        assert_eq!(fix.apply(), "// fixed\nlet a = bar();\nlet b = bar(2);\n");
+
+        // touching suggestions are fine, overlapping ones are rejected
+        assert!(fix.add(11..12, "[").is_ok());
+        assert_eq!(
+            fix.add(10..12, "x"),
+            Err(InvalidSuggestion::Overlap {
+                existing: 8..11,
+                new: 10..12
+            })
+        );
+        assert!(fix.add(0..0, "x").is_err());
+        assert!(fix.add(25..25, "x").is_err());
+        assert_eq!(fix.suggestions().len(), 5);
+
+        // ranges which can't be applied to the source
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = 4..2;
+        assert_eq!(
+            fix.add(reversed.clone(), "x"),
+            Err(InvalidSuggestion::Reversed(reversed))
+        );
+        assert_eq!(
+            fix.add(4..40, "x"),
+            Err(InvalidSuggestion::OutOfBounds(4..40))
+        );
+        let src = "ä";
+        let lines = LineCache::new(src);
+        let mut fix = Fix::new(src, &lines);
+        assert_eq!(
+            fix.add(1..2, "a"),
+            Err(InvalidSuggestion::NotCharBoundary(1..2))
+        );
+        assert_eq!(
+            alloc::format!("{}", fix.add(1..3, "a").unwrap_err()),
+            "suggestion for 1..3 reaches past the end of the source"
+        );
+    }
+
+    fn render(src: &str, style: DiffStyle, f: impl FnOnce(&mut Fix<'_>)) -> String {
+        let lines = LineCache::new(src);
+        let mut fix = Fix::new(src, &lines);
+        f(&mut fix);
+        let renderer = Renderer {
+            diff_style: style,
+            ..Renderer::default()
+        };
+        renderer.render_fix(&fix)
+    }
+
+    #[test]
+    fn lines() {
+        let src = "fn main() {\n\tlet x = foo;\n\tx\n}\n";
+        let out = render(src, DiffStyle::Lines, |fix| {
+            fix.add(21..24, "bar").unwrap();
+        });
+        assert_eq!(out, "  |\n2 -     let x = foo;\n2 +     let x = bar;\n");
+
+        // removed and added lines shift the numbers of the new lines
+        let src = "a\nb\nc\nd\ne\nf\n";
+        let out = render(src, DiffStyle::Lines, |fix| {
+            fix.add(2..4, "").unwrap();
+            fix.add(6..7, "D\nD2").unwrap();
+            fix.add(8..9, "E").unwrap();
+        });
+        assert_eq!(out, "  |\n2 - b\n...\n4 - d\n5 - e\n3 + D\n4 + D2\n5 + E\n");
+    }
+
+    #[test]
+    fn inline() {
+        let src = "fn main() {\n\tlet x = foo;\n\tx\n}\n";
+        let out = render(src, DiffStyle::Inline, |fix| {
+            fix.add(21..24, "barbaz").unwrap();
+            fix.add(17..17, "mut ").unwrap();
+        });
+        assert_eq!(
+            out,
+            "  |\n2 |     let mut x = barbaz;\n  |         ++++    ~~~~~~\n"
+        );
+
+        // deletions fall back to lines
+        let out = render(src, DiffStyle::Inline, |fix| {
+            fix.add(16..17, "").unwrap();
+        });
+        assert_eq!(out, "  |\n2 -     let x = foo;\n2 +     letx = foo;\n");
+    }
+}
@@ -0,0 +1,932 @@
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+//! Rendering of annotated source excerpts, in the style of rustc.
+//!
+//! ```
+//! use linetrack::{render::{Level, Renderer, Snippet}, LineCache};
+//!
+//! let src = "let x: u32 = \"a\";\n";
+//! let lines = LineCache::new(src);
+//! let mut snippet = Snippet::new(src, &lines);
+//! snippet.set_title(Level::Error, "mismatched types");
+//! snippet.set_origin("src/main.rs");
+//! snippet.add_label(13..16, true, "expected `u32`, found `&str`");
+//! snippet.add_label(7..10, false, "expected due to this");
+//! assert_eq!(
+//!     Renderer::default().render(&snippet),
+//!     "\
+//! error: mismatched types
+//!  --> src/main.rs:1:14
+//!   |
+//! 1 | let x: u32 = \"a\";
+//!   |        ---   ^^^ expected `u32`, found `&str`
+//!   |        |
+//!   |        expected due to this
+//! "
+//! );
+//! ```
+
+mod excerpt;
+mod fix;
+mod style;
+pub use excerpt::{excerpt, Excerpt};
+pub use fix::{DiffStyle, Fix, InvalidSuggestion, Suggestion};
+pub use style::{Color, Style, Theme};
+
+use crate::{ColumnUnit, LineCache};
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use core::ops::Range;
+
+/// The severity of a diagnostic or note.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::Note => "note",
+            Level::Help => "help",
+        })
+    }
+}
+
+/// A labelled byte span of a [`Snippet`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Label {
+    pub range: Range<usize>,
+    /// primary labels are underlined with `^`, secondary ones with `-`
+    pub primary: bool,
+    pub message: String,
+}
+
+/// A source excerpt to be rendered: a source with labelled byte spans,
+/// an optional title and origin, and trailing notes.
+#[derive(Clone, Debug)]
+pub struct Snippet<'a> {
+    src: &'a str,
+    lines: &'a LineCache,
+    title: Option<(Level, String)>,
+    origin: Option<String>,
+    labels: Vec<Label>,
+    notes: Vec<(Level, String)>,
+}
+
+impl<'a> Snippet<'a> {
+    /// `lines` has to be the line cache of `src`.
+    #[inline]
+    pub fn new(src: &'a str, lines: &'a LineCache) -> Self {
+        Self {
+            src,
+            lines,
+            title: None,
+            origin: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn set_title(&mut self, level: Level, title: impl Into<String>) {
+        self.title = Some((level, title.into()));
+    }
+
+    /// sets the name of the source, usually a file path,
+    /// which is printed together with the position of the first primary label.
+    #[inline]
+    pub fn set_origin(&mut self, origin: impl Into<String>) {
+        self.origin = Some(origin.into());
+    }
+
+    /// adds a label; the message may be empty, in which case only the span
+    /// is underlined.
+    ///
+    /// # Panics
+    /// Panics if `range` is reversed, reaches past the end of the source,
+    /// or doesn't lie on `char` boundaries.
+    pub fn add_label(&mut self, range: Range<usize>, primary: bool, message: impl Into<String>) {
+        assert!(
+            range.start <= range.end
+                && self.src.is_char_boundary(range.start)
+                && self.src.is_char_boundary(range.end),
+            "label span {:?} doesn't lie within the source",
+            range
+        );
+        self.labels.push(Label {
+            range,
+            primary,
+            message: message.into(),
+        });
+    }
+
+    /// adds a note which is printed after the excerpt, like `= note: ...`
+    #[inline]
+    pub fn add_note(&mut self, level: Level, note: impl Into<String>) {
+        self.notes.push((level, note.into()));
+    }
+
+    #[inline(always)]
+    pub fn labels(&self) -> &[Label] {
+        &self.labels
+    }
+}
+
+/// Settings for rendering [`Snippet`]s.
+#[derive(Clone, Debug)]
+pub struct Renderer {
+    /// tabs in the source are expanded to the next multiple of this
+    pub tab_width: usize,
+    /// lines inside of a multi-line label which carry no label themselves
+    /// are elided if there are more than this many of them in a row;
+    /// the first and last of them are still shown.
+    pub fold_threshold: usize,
+    /// the styles to use, the default theme doesn't add any escape sequences
+    pub theme: Theme,
+    /// how [`Fix`]es are shown
+    pub diff_style: DiffStyle,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self {
+            tab_width: 4,
+            fold_threshold: 4,
+            theme: Theme::plain(),
+            diff_style: DiffStyle::default(),
+        }
+    }
+}
+
+/// the unit in which the renderer measures columns
+#[cfg(feature = "unicode-width")]
+const DISPLAY_UNIT: ColumnUnit = ColumnUnit::Width;
+#[cfg(not(feature = "unicode-width"))]
+const DISPLAY_UNIT: ColumnUnit = ColumnUnit::Char;
+
+/// what a part of the output shows, which determines its [`Style`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Plain,
+    Source,
+    Gutter,
+    Title,
+    /// the level in the title
+    Level(Level),
+    /// primary underlines and labels, styled like the level of the title
+    Primary,
+    Secondary,
+    /// text added or removed by a suggestion
+    Addition,
+    Removal,
+}
+
+type Cell = (char, Kind);
+
+/// a label resolved to lines and display columns
+struct Placed<'l> {
+    label: &'l Label,
+    start: (usize, usize),
+    /// the position after the last column of the label
+    end: (usize, usize),
+    /// the column of the rail on the left side, for multi-line labels
+    rail: Option<usize>,
+    /// multi-line labels which start in front of all text in their first line
+    /// are drawn with a `/` instead of a connector to their start
+    slash: bool,
+}
+
+impl Placed<'_> {
+    #[inline]
+    fn marker(&self) -> Cell {
+        if self.label.primary {
+            ('^', Kind::Primary)
+        } else {
+            ('-', Kind::Secondary)
+        }
+    }
+
+    #[inline]
+    fn kind(&self) -> Kind {
+        self.marker().1
+    }
+}
+
+/// a row of annotations below a source line
+struct Row {
+    /// the rails, followed by the underlines
+    cells: Vec<Cell>,
+    /// the rails take up this many cells at the start
+    text_start: usize,
+}
+
+impl Row {
+    /// creates a row with the given rails drawn
+    fn new(rails: &[Option<Kind>]) -> Self {
+        let mut cells: Vec<Cell> = rails
+            .iter()
+            .map(|rail| rail.map_or((' ', Kind::Plain), |k| ('|', k)))
+            .collect();
+        if !cells.is_empty() {
+            cells.push((' ', Kind::Plain));
+        }
+        let text_start = cells.len();
+        Self { cells, text_start }
+    }
+
+    /// sets the cell at the text column `col`
+    fn put(&mut self, col: usize, cell: Cell) {
+        let idx = self.text_start + col;
+        if self.cells.len() <= idx {
+            self.cells.resize(idx + 1, (' ', Kind::Plain));
+        }
+        self.cells[idx] = cell;
+    }
+
+    /// draws a horizontal connector from the right of `rail`
+    /// up to (excluding) the text column `col`, without crossing other rails
+    fn connect(&mut self, rail: usize, col: usize, kind: Kind) {
+        for cell in &mut self.cells[rail + 1..self.text_start] {
+            if cell.0 == ' ' {
+                *cell = ('_', kind);
+            }
+        }
+        for c in 0..col {
+            self.put(c, ('_', kind));
+        }
+    }
+
+    /// appends a message at the text column `col`, or right after the
+    /// current contents if they already extend past it
+    fn push_message(&mut self, col: usize, message: &str, kind: Kind) {
+        let at = self.text_start + col;
+        if self.cells.len() < at {
+            self.cells.resize(at, (' ', Kind::Plain));
+        }
+        self.cells.extend(message.chars().map(|c| (c, kind)));
+    }
+}
+
+/// writes rows of styled cells
+struct Output<'a> {
+    out: &'a mut dyn Write,
+    theme: &'a Theme,
+    /// the level of the title
+    level: Level,
+    gutter: usize,
+}
+
+impl Output<'_> {
+    fn style(&self, kind: Kind) -> Style {
+        let theme = self.theme;
+        match kind {
+            Kind::Plain => Style::default(),
+            Kind::Source => theme.source,
+            Kind::Gutter => theme.gutter,
+            Kind::Title => theme.title,
+            Kind::Level(level) => theme.level(level),
+            Kind::Primary => theme.level(self.level),
+            Kind::Secondary => theme.secondary,
+            Kind::Addition => theme.addition,
+            Kind::Removal => theme.removal,
+        }
+    }
+
+    /// writes a line, without trailing whitespace
+    fn line(&mut self, cells: &[Cell]) -> fmt::Result {
+        let len = cells.len() - cells.iter().rev().take_while(|c| c.0 == ' ').count();
+        let cells = &cells[..len];
+        // spaces look the same in any style, so they take the style
+        // of the following text to avoid switching styles for them
+        let mut kinds: Vec<Kind> = cells.iter().map(|c| c.1).collect();
+        for i in (0..cells.len().saturating_sub(1)).rev() {
+            if cells[i].0 == ' ' {
+                kinds[i] = kinds[i + 1];
+            }
+        }
+        let mut i = 0;
+        while i < cells.len() {
+            let k = kinds[i];
+            let run = kinds[i..].iter().take_while(|&&c| c == k).count();
+            let style = self.style(k);
+            style.write_start(self.out)?;
+            for &(c, _) in &cells[i..i + run] {
+                self.out.write_char(c)?;
+            }
+            style.write_end(self.out)?;
+            i += run;
+        }
+        self.out.write_char('\n')
+    }
+
+    /// writes a line of the excerpt, like `12 | text`
+    fn row(&mut self, lnum: Option<usize>, sep: char, text: &[Cell]) -> fmt::Result {
+        let lnum = match lnum {
+            Some(l) => format!("{:>w$}", l + 1, w = self.gutter),
+            None => format!("{:w$}", "", w = self.gutter),
+        };
+        let mut cells: Vec<Cell> = lnum.chars().map(|c| (c, Kind::Gutter)).collect();
+        cells.extend([(' ', Kind::Plain), (sep, Kind::Gutter), (' ', Kind::Plain)]);
+        cells.extend_from_slice(text);
+        self.line(&cells)
+    }
+}
+
+/// converts a string into cells of a single kind
+fn cells(s: &str, kind: Kind) -> Vec<Cell> {
+    s.chars().map(|c| (c, kind)).collect()
+}
+
+impl Renderer {
+    /// renders the snippet into a string
+    pub fn render(&self, snippet: &Snippet<'_>) -> String {
+        let mut out = String::new();
+        // writing into a `String` can't fail
+        let _ = self.render_to(snippet, &mut out);
+        out
+    }
+
+    /// renders the snippet into `out`
+    pub fn render_to(&self, snippet: &Snippet<'_>, out: &mut dyn Write) -> fmt::Result {
+        let src = snippet.src;
+        let lines = snippet.lines;
+        let mut placed: Vec<_> = snippet
+            .labels
+            .iter()
+            .map(|label| self.place(snippet, label))
+            .collect();
+        let rails = assign_rails(&mut placed);
+
+        // lines containing the start or end of a label
+        let anchors: BTreeSet<usize> = placed.iter().flat_map(|p| [p.start.0, p.end.0]).collect();
+        let mut out = Output {
+            out,
+            theme: &self.theme,
+            level: snippet.title.as_ref().map_or(Level::Error, |t| t.0),
+            gutter: anchors.last().map_or(1, |&l| digits(l + 1)),
+        };
+        let gutter = out.gutter;
+
+        if let Some((level, title)) = &snippet.title {
+            let mut row = cells(&format!("{}", level), Kind::Level(*level));
+            row.extend(cells(&format!(": {}", title), Kind::Title));
+            out.line(&row)?;
+        }
+        if let Some(origin) = &snippet.origin {
+            let mut row = cells(&format!("{:w$}-->", "", w = gutter), Kind::Gutter);
+            let main = placed.iter().find(|p| p.label.primary).or(placed.first());
+            let origin = match main {
+                Some(p) => {
+                    let (line, col) = lines.run_with(src, p.label.range.start, ColumnUnit::Char);
+                    format!(" {}:{}:{}", origin, line + 1, col + 1)
+                }
+                None => format!(" {}", origin),
+            };
+            row.extend(cells(&origin, Kind::Plain));
+            out.line(&row)?;
+        }
+        if !anchors.is_empty() {
+            out.row(None, '|', &[])?;
+        }
+
+        let mut prev: Option<usize> = None;
+        for &line in &anchors {
+            if let Some(p) = prev {
+                let gap = p + 1..line;
+                let inside = placed
+                    .iter()
+                    .any(|pl| pl.rail.is_some() && pl.start.0 <= p && line <= pl.end.0);
+                if inside && gap.len() <= self.fold_threshold.max(2) {
+                    for l in gap {
+                        self.render_line(snippet, &placed, rails, l, &mut out)?;
+                    }
+                } else if inside {
+                    self.render_line(snippet, &placed, rails, gap.start, &mut out)?;
+                    let mut row = cells(&format!("{:<w$}", "...", w = gutter + 3), Kind::Gutter);
+                    row.extend(Row::new(&passing_rails(&placed, rails, gap.start + 1)).cells);
+                    out.line(&row)?;
+                    self.render_line(snippet, &placed, rails, gap.end - 1, &mut out)?;
+                } else if gap.len() == 1 {
+                    // show a single skipped line instead of eliding it
+                    self.render_line(snippet, &placed, rails, gap.start, &mut out)?;
+                } else if gap.len() > 1 {
+                    out.line(&cells("...", Kind::Gutter))?;
+                }
+            }
+            self.render_line(snippet, &placed, rails, line, &mut out)?;
+            prev = Some(line);
+        }
+
+        if !snippet.notes.is_empty() {
+            out.row(None, '|', &[])?;
+        }
+        for (level, note) in &snippet.notes {
+            let mut row = cells(&format!("{}", level), Kind::Title);
+            row.extend(cells(&format!(": {}", note), Kind::Plain));
+            out.row(None, '=', &row)?;
+        }
+        Ok(())
+    }
+
+    fn place<'l>(&self, snippet: &Snippet<'_>, label: &'l Label) -> Placed<'l> {
+        let start = self.display_pos(snippet, label.range.start);
+        let mut end = self.display_pos(snippet, label.range.end);
+        if end.0 > start.0 && end.1 == 0 {
+            // the label ends with a line terminator, point just behind
+            // the contents of that line instead of in front of the next one
+            let text = self.line_text(snippet, end.0 - 1);
+            end = (end.0 - 1, DISPLAY_UNIT.count(text.as_bytes()) + 1);
+        }
+        let multiline = end.0 > start.0;
+        let slash = multiline && {
+            let text = self.line_text(snippet, start.0);
+            let indent = text.len() - text.trim_start().len();
+            start.1 <= indent
+        };
+        Placed {
+            label,
+            start,
+            end,
+            rail: None,
+            slash,
+        }
+    }
+
+    /// returns the line and display column of `pos`
+    fn display_pos(&self, snippet: &Snippet<'_>, pos: usize) -> (usize, usize) {
+        let (line, _) = snippet.lines.run(pos);
+        let start = snippet.lines.line_start(line).unwrap_or(0);
+        // stop at the end of the line contents, a terminator isn't displayed
+        let end = snippet
+            .lines
+            .line_range(line)
+            .map_or(pos, |r| r.end.min(pos));
+        let col = DISPLAY_UNIT.advance(&snippet.src.as_bytes()[start..end], 0, self.tab_width);
+        (line, col)
+    }
+
+    /// returns the contents of `line`, with tabs expanded
+    fn line_text(&self, snippet: &Snippet<'_>, line: usize) -> String {
+        let range = snippet.lines.line_range(line).unwrap_or(0..0);
+        self.expand_tabs(&snippet.src[range])
+    }
+
+    /// returns `text` with tabs expanded
+    fn expand_tabs(&self, text: &str) -> String {
+        let mut expanded = String::new();
+        let mut col = 0;
+        for c in text.chars() {
+            if c == '\t' {
+                let stop = crate::next_tab_stop(col, self.tab_width);
+                expanded.extend(core::iter::repeat_n(' ', stop - col));
+                col = stop;
+            } else {
+                expanded.push(c);
+                col += DISPLAY_UNIT.char_width(c);
+            }
+        }
+        expanded
+    }
+
+    fn render_line(
+        &self,
+        snippet: &Snippet<'_>,
+        placed: &[Placed<'_>],
+        rails: usize,
+        line: usize,
+        out: &mut Output<'_>,
+    ) -> fmt::Result {
+        let text = self.line_text(snippet, line);
+
+        // rails which are drawn on the following rows,
+        // labels ending in this line still need them to reach their end
+        let mut active: Vec<Option<Kind>> = alloc::vec![None; rails];
+        let mut source = Row::new(&active);
+        for p in placed {
+            if let Some(rail) = p.rail {
+                if p.start.0 < line && line <= p.end.0 {
+                    source.cells[rail] = ('|', p.kind());
+                    active[rail] = Some(p.kind());
+                } else if p.start.0 == line && p.slash {
+                    source.cells[rail] = ('/', p.kind());
+                    active[rail] = Some(p.kind());
+                }
+            }
+        }
+        source.push_message(0, &text, Kind::Source);
+        out.row(Some(line), '|', &source.cells)?;
+
+        // single-line labels: (start column, end column, marker, message)
+        let marks: Vec<(usize, usize, Cell, &str)> = placed
+            .iter()
+            .filter(|p| p.rail.is_none() && p.start.0 == line)
+            .map(|p| {
+                (
+                    p.start.1,
+                    p.end.1.max(p.start.1 + 1),
+                    p.marker(),
+                    &p.label.message[..],
+                )
+            })
+            .collect();
+        if !marks.is_empty() {
+            let mut first = Row::new(&active);
+            // secondary first, so that primary markers win
+            for &(start, end, marker, _) in marks
+                .iter()
+                .filter(|m| m.2 .1 != Kind::Primary)
+                .chain(marks.iter().filter(|m| m.2 .1 == Kind::Primary))
+            {
+                for col in start..end {
+                    first.put(col, marker);
+                }
+            }
+
+            // messages, from right to left; the rightmost one goes directly
+            // after the underlines if nothing extends past its end. The others
+            // are stacked below, connected to their label by a `|`, so each
+            // of them only has the connectors of labels to its left beside it.
+            let mut messages: Vec<_> = marks.iter().filter(|m| !m.3.is_empty()).collect();
+            messages.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)));
+            if let Some(&&m) = messages.first() {
+                if marks.iter().all(|o| o.1 <= m.1) {
+                    first.push_message(0, " ", Kind::Plain);
+                    first.push_message(0, m.3, m.2 .1);
+                    messages.remove(0);
+                }
+            }
+            out.row(None, '|', &first.cells)?;
+
+            // labels starting in the same column share their connector,
+            // the message of the shorter label goes last
+            let mut rest = &messages[..];
+            let connectors = |rest: &[&(usize, usize, Cell, &str)], before: usize| {
+                let mut row = Row::new(&active);
+                for m in rest.iter().filter(|m| m.0 < before) {
+                    row.put(m.0, ('|', m.2 .1));
+                }
+                row
+            };
+            if !rest.is_empty() {
+                out.row(None, '|', &connectors(rest, usize::MAX).cells)?;
+            }
+            while let Some((m, tail)) = rest.split_first() {
+                let mut row = connectors(tail, m.0);
+                row.push_message(m.0, m.3, m.2 .1);
+                out.row(None, '|', &row.cells)?;
+                rest = tail;
+            }
+        }
+
+        // multi-line labels ending here, the innermost first
+        let mut ending: Vec<_> = placed
+            .iter()
+            .filter(|p| p.rail.is_some() && p.end.0 == line)
+            .collect();
+        ending.sort_by_key(|p| core::cmp::Reverse(p.rail));
+        for p in ending {
+            let rail = p.rail.unwrap_or(0);
+            let mut row = Row::new(&active);
+            let last = p.end.1.saturating_sub(1);
+            row.connect(rail, last, p.kind());
+            row.put(last, p.marker());
+            if !p.label.message.is_empty() {
+                row.push_message(0, " ", Kind::Plain);
+                row.push_message(0, &p.label.message, p.kind());
+            }
+            out.row(None, '|', &row.cells)?;
+            active[rail] = None;
+        }
+
+        // multi-line labels starting here, the outermost first
+        let mut starting: Vec<_> = placed
+            .iter()
+            .filter(|p| p.rail.is_some() && p.start.0 == line && !p.slash)
+            .collect();
+        starting.sort_by_key(|p| p.rail);
+        for p in starting {
+            let rail = p.rail.unwrap_or(0);
+            let mut row = Row::new(&active);
+            row.connect(rail, p.start.1, p.kind());
+            row.put(p.start.1, p.marker());
+            out.row(None, '|', &row.cells)?;
+            active[rail] = Some(p.kind());
+        }
+        Ok(())
+    }
+}
+
+/// returns the rails of the multi-line labels
+/// which pass through `line` without starting or ending in it
+fn passing_rails(placed: &[Placed<'_>], rails: usize, line: usize) -> Vec<Option<Kind>> {
+    let mut active = alloc::vec![None; rails];
+    for p in placed {
+        if let Some(rail) = p.rail {
+            if p.start.0 < line && line < p.end.0 {
+                active[rail] = Some(p.kind());
+            }
+        }
+    }
+    active
+}
+
+/// assigns rails to multi-line labels, so that overlapping ones get
+/// different rails and outer labels are left of inner ones.
+/// Returns the number of rails needed.
+fn assign_rails(placed: &mut [Placed<'_>]) -> usize {
+    let mut order: Vec<usize> = (0..placed.len())
+        .filter(|&i| placed[i].end.0 > placed[i].start.0)
+        .collect();
+    order.sort_by_key(|&i| {
+        (
+            placed[i].label.range.start,
+            core::cmp::Reverse(placed[i].label.range.end),
+        )
+    });
+    // the last line occupied by each rail
+    let mut busy: Vec<usize> = Vec::new();
+    for i in order {
+        let (start, end) = (placed[i].start.0, placed[i].end.0);
+        let rail = match busy.iter().position(|&until| until < start) {
+            Some(rail) => rail,
+            None => {
+                busy.push(0);
+                busy.len() - 1
+            }
+        };
+        busy[rail] = end;
+        placed[i].rail = Some(rail);
+    }
+    busy.len()
+}
+
+/// returns the number of decimal digits of `n`
+fn digits(mut n: usize) -> usize {
+    let mut d = 1;
+    while n >= 10 {
+        n /= 10;
+        d += 1;
+    }
+    d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(src: &str, f: impl FnOnce(&mut Snippet<'_>)) -> String {
+        let lines = LineCache::new(src);
+        let mut snippet = Snippet::new(src, &lines);
+        f(&mut snippet);
+        Renderer::default().render(&snippet)
+    }
+
+    #[test]
+    fn labels_and_notes() {
+        let src = "fn main() {\n\tlet x = foo(1, 2);\n}\n";
+        let out = render(src, |s| {
+            s.set_title(Level::Error, "wrong number of arguments");
+            s.set_origin("main.rs");
+            s.add_label(21..24, true, "expected 1 argument");
+            s.add_label(25..29, false, "");
+            s.add_note(Level::Note, "`foo` is defined elsewhere");
+        });
+        assert_eq!(
+            out,
+            "\
+error: wrong number of arguments
+ --> main.rs:2:10
+  |
+2 |     let x = foo(1, 2);
+  |             ^^^ ----
+  |             |
+  |             expected 1 argument
+  |
+  = note: `foo` is defined elsewhere
+"
+        );
+    }
+
+    #[test]
+    fn elided_lines() {
+        let src = "a\nb\nc\nd\ne\nf\n";
+        let out = render(src, |s| {
+            s.set_title(Level::Warning, "letters");
+            s.add_label(0..1, true, "here");
+            s.add_label(4..5, false, "and here");
+            s.add_label(10..11, false, "");
+        });
+        assert_eq!(
+            out,
+            "\
+warning: letters
+  |
+1 | a
+  | ^ here
+2 | b
+3 | c
+  | - and here
+...
+6 | f
+  | -
+"
+        );
+    }
+
+    #[test]
+    fn empty_span() {
+        let src = "let s = \"abc\ndef\";\n";
+        let out = render(src, |s| {
+            s.set_title(Level::Error, "unterminated string");
+            s.add_label(8..17, true, "string");
+            s.add_label(4..4, false, "empty");
+        });
+        assert_eq!(
+            out,
+            "\
+error: unterminated string
+  |
+1 |   let s = \"abc
+  |       - empty
+  |  _________^
+2 | | def\";
+  | |____^ string
+"
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn reversed_span() {
+        #[allow(clippy::reversed_empty_ranges)]
+        render("let x = 1;\nfoo\n", |s| s.add_label(5..2, true, ""));
+    }
+
+    #[test]
+    #[should_panic]
+    fn span_out_of_bounds() {
+        render("let x = 1;\nfoo\n", |s| s.add_label(100..200, true, ""));
+    }
+
+    #[test]
+    #[should_panic]
+    fn span_inside_char() {
+        render("ä", |s| s.add_label(0..1, true, ""));
+    }
+
+    #[test]
+    fn multiline_rails() {
+        let src = "fn main() {\n    foo();\n    bar();\n}\n";
+        let out = render(src, |s| {
+            s.set_title(Level::Error, "nested");
+            s.add_label(0..36, false, "outer");
+            s.add_label(16..29, true, "inner");
+            s.add_label(4..8, false, "name");
+        });
+        assert_eq!(
+            out,
+            "\
+error: nested
+  |
+1 | /  fn main() {
+  | |      ---- name
+2 | |/     foo();
+3 | ||     bar();
+  | ||______^ inner
+4 | |  }
+  | |___- outer
+"
+        );
+
+        let out = render(src, |s| {
+            s.set_title(Level::Error, "unclosed");
+            // ends with the newline, which is pointed at behind the `}`
+            s.add_label(10..src.len(), true, "");
+        });
+        assert_eq!(
+            out,
+            "\
+error: unclosed
+  |
+1 |   fn main() {
+  |  ___________^
+2 | |     foo();
+3 | |     bar();
+4 | | }
+  | |__^
+"
+        );
+    }
+
+    #[test]
+    fn multiline_folding() {
+        let src = "{\n1\n2\n3\n4\n5\n6\n7\n}\n";
+        let out = render(src, |s| {
+            s.set_title(Level::Error, "long block");
+            s.add_label(0..17, true, "block");
+            s.add_label(2..3, false, "one");
+        });
+        assert_eq!(
+            out,
+            "\
+error: long block
+  |
+1 | / {
+2 | | 1
+  | | - one
+3 | | 2
+... |
+8 | | 7
+9 | | }
+  | |_^ block
+"
+        );
+    }
+
+    #[cfg(feature = "unicode-width")]
+    #[test]
+    fn wide_characters() {
+        let src = "漢字 = 1;\n";
+        let out = render(src, |s| s.add_label(7..8, true, "eq"));
+        assert_eq!(out, "  |\n1 | 漢字 = 1;\n  |      ^ eq\n");
+    }
+
+    #[test]
+    fn overlapping_labels() {
+        let src = "let v = foo(bar, baz);\n";
+        let out = render(src, |s| {
+            s.set_title(Level::Error, "overlap");
+            s.add_label(8..11, true, "called here");
+            s.add_label(12..15, false, "first");
+            s.add_label(17..20, false, "second");
+            s.add_label(8..21, false, "the call");
+            s.add_label(12..20, false, "");
+        });
+        assert_eq!(
+            out,
+            "\
+error: overlap
+  |
+1 | let v = foo(bar, baz);
+  |         ^^^----------
+  |         |   |    |
+  |         |   |    second
+  |         |   first
+  |         the call
+  |         called here
+"
+        );
+    }
+
+    #[test]
+    fn themes() {
+        let src = "let x = y;\n";
+        let lines = LineCache::new(src);
+        let mut snippet = Snippet::new(src, &lines);
+        snippet.set_title(Level::Warning, "unused");
+        snippet.add_label(4..5, true, "here");
+        snippet.add_label(8..9, false, "");
+        snippet.add_note(Level::Help, "remove it");
+
+        let plain = Renderer::default().render(&snippet);
+        assert!(!plain.contains('\x1b'));
+
+        let renderer = Renderer {
+            theme: Theme::rustc(),
+            ..Renderer::default()
+        };
+        let colored = renderer.render(&snippet);
+        assert_eq!(
+            colored,
+            "\
+\x1b[1;93mwarning\x1b[0m\x1b[1m: unused\x1b[0m
+\x1b[1;94m  |\x1b[0m
+\x1b[1;94m1 |\x1b[0m let x = y;
+\x1b[1;94m  |\x1b[0m\x1b[1;93m     ^\x1b[0m\x1b[1;94m   -\x1b[0m
+\x1b[1;94m  |\x1b[0m\x1b[1;93m     |\x1b[0m
+\x1b[1;94m  |\x1b[0m\x1b[1;93m     here\x1b[0m
+\x1b[1;94m  |\x1b[0m
+\x1b[1;94m  =\x1b[0m\x1b[1m help\x1b[0m: remove it
+"
+        );
+
+        // stripping the escape sequences gives the plain output
+        let mut stripped = String::new();
+        let mut rest = &colored[..];
+        while let Some(at) = rest.find('\x1b') {
+            stripped.push_str(&rest[..at]);
+            rest = &rest[at + rest[at..].find('m').unwrap() + 1..];
+        }
+        stripped.push_str(rest);
+        assert_eq!(stripped, plain);
+    }
+}
@@ -0,0 +1,181 @@
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+use super::Level;
+use core::fmt;
+
+/// One of the 16 basic ANSI terminal colors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+}
+
+impl Color {
+    /// returns the SGR parameter selecting this color as foreground
+    fn fg_code(self) -> u8 {
+        use Color::*;
+        match self {
+            Black => 30,
+            Red => 31,
+            Green => 32,
+            Yellow => 33,
+            Blue => 34,
+            Magenta => 35,
+            Cyan => 36,
+            White => 37,
+            BrightBlack => 90,
+            BrightRed => 91,
+            BrightGreen => 92,
+            BrightYellow => 93,
+            BrightBlue => 94,
+            BrightMagenta => 95,
+            BrightCyan => 96,
+            BrightWhite => 97,
+        }
+    }
+}
+
+/// The styling of a part of the rendered output.
+///
+/// The default style is plain text, which is printed without any
+/// escape sequences.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bold: bool,
+}
+
+impl Style {
+    #[inline]
+    pub const fn new(fg: Color, bold: bool) -> Self {
+        Self { fg: Some(fg), bold }
+    }
+
+    #[inline]
+    pub fn is_plain(&self) -> bool {
+        self.fg.is_none() && !self.bold
+    }
+
+    /// writes the escape sequence switching to this style
+    pub(crate) fn write_start(&self, f: &mut dyn fmt::Write) -> fmt::Result {
+        if self.is_plain() {
+            return Ok(());
+        }
+        f.write_str("\x1b[")?;
+        if self.bold {
+            f.write_str("1")?;
+        }
+        if let Some(fg) = self.fg {
+            if self.bold {
+                f.write_str(";")?;
+            }
+            write!(f, "{}", fg.fg_code())?;
+        }
+        f.write_str("m")
+    }
+
+    /// writes the escape sequence switching back to plain text
+    pub(crate) fn write_end(&self, f: &mut dyn fmt::Write) -> fmt::Result {
+        if self.is_plain() {
+            Ok(())
+        } else {
+            f.write_str("\x1b[0m")
+        }
+    }
+}
+
+/// The styles used by the renderer.
+///
+/// The default theme is [`plain`](Self::plain), which doesn't emit any
+/// escape sequences, use [`rustc`](Self::rustc) for colored output.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Theme {
+    /// the severities; primary underlines and labels
+    /// use the style of the severity in the title
+    pub error: Style,
+    pub warning: Style,
+    pub note: Style,
+    pub help: Style,
+    /// the message of the title, and the level of notes
+    pub title: Style,
+    /// line numbers and the separators next to them
+    pub gutter: Style,
+    /// secondary underlines and labels
+    pub secondary: Style,
+    /// the source text
+    pub source: Style,
+    /// text inserted by a suggestion
+    pub addition: Style,
+    /// text removed by a suggestion
+    pub removal: Style,
+}
+
+impl Theme {
+    /// a theme without any styling
+    #[inline]
+    pub fn plain() -> Self {
+        Self::default()
+    }
+
+    /// the colors used by rustc and cargo
+    pub fn rustc() -> Self {
+        Self {
+            error: Style::new(Color::BrightRed, true),
+            warning: Style::new(Color::BrightYellow, true),
+            note: Style::new(Color::BrightGreen, true),
+            help: Style::new(Color::BrightCyan, true),
+            title: Style {
+                fg: None,
+                bold: true,
+            },
+            gutter: Style::new(Color::BrightBlue, true),
+            secondary: Style::new(Color::BrightBlue, true),
+            source: Style::default(),
+            addition: Style::new(Color::Green, false),
+            removal: Style::new(Color::Red, false),
+        }
+    }
+
+    /// returns the style of the given severity
+    #[inline]
+    pub fn level(&self, level: Level) -> Style {
+        match level {
+            Level::Error => self.error,
+            Level::Warning => self.warning,
+            Level::Note => self.note,
+            Level::Help => self.help,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+
+    #[test]
+    fn escapes() {
+        let mut s = String::new();
+        let style = Style::new(Color::BrightRed, true);
+        style.write_start(&mut s).unwrap();
+        s.push('x');
+        style.write_end(&mut s).unwrap();
+        Style::new(Color::Blue, false).write_start(&mut s).unwrap();
+        Style::default().write_start(&mut s).unwrap();
+        assert_eq!(s, "\x1b[1;91mx\x1b[0m\x1b[34m");
+    }
+}
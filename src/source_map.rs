@@ -0,0 +1,191 @@
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+use crate::{ColumnUnit, LineCache, LineTerminators};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Identifies a file in a [`SourceMap`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(usize);
+
+impl FileId {
+    /// returns the index of the file, in the order the files were added
+    #[inline(always)]
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// A file registered in a [`SourceMap`], together with its line cache.
+#[derive(Clone, Debug)]
+pub struct SourceFile {
+    name: String,
+    src: String,
+    base: usize,
+    lines: LineCache,
+}
+
+impl SourceFile {
+    #[inline(always)]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[inline(always)]
+    pub fn src(&self) -> &str {
+        &self.src
+    }
+
+    /// returns the global offset of the first byte of this file
+    #[inline(always)]
+    pub fn base(&self) -> usize {
+        self.base
+    }
+
+    #[inline(always)]
+    pub fn lines(&self) -> &LineCache {
+        &self.lines
+    }
+
+    /// returns `true` if the global offset `pos` lies in this file,
+    /// the end of the file included
+    #[inline]
+    pub fn contains(&self, pos: usize) -> bool {
+        pos >= self.base && pos - self.base <= self.src.len()
+    }
+}
+
+/// A registry of source files, which places all of them into
+/// a single global offset space.
+///
+/// Each file occupies the global offsets from its [`base`](SourceFile::base)
+/// up to and including the end of the file, and the next file starts right
+/// after that, so that the end of each file has a distinct global offset.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+    terminators: LineTerminators,
+}
+
+impl SourceMap {
+    /// creates an empty source map, which only treats `\n` as line terminator
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn with_terminators(terminators: LineTerminators) -> Self {
+        Self {
+            files: Vec::new(),
+            terminators,
+        }
+    }
+
+    /// adds a file, returning its id. The file is placed after all
+    /// previously added files in the global offset space.
+    pub fn add_file(&mut self, name: impl Into<String>, src: impl Into<String>) -> FileId {
+        let src = src.into();
+        let base = self
+            .files
+            .last()
+            .map_or(0, |last| last.base + last.src.len() + 1);
+        let id = FileId(self.files.len());
+        self.files.push(SourceFile {
+            name: name.into(),
+            lines: LineCache::with_terminators(&src, self.terminators),
+            src,
+            base,
+        });
+        id
+    }
+
+    /// returns the file with the given id
+    ///
+    /// # Panics
+    /// Panics if `id` doesn't belong to this map.
+    #[inline]
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0]
+    }
+
+    /// returns an iterator over all files, in the order they were added
+    pub fn files(&self) -> impl Iterator<Item = (FileId, &SourceFile)> + '_ {
+        self.files.iter().enumerate().map(|(i, f)| (FileId(i), f))
+    }
+
+    /// returns the file the global offset `pos` lies in,
+    /// or `None` if it is past the end of the last one.
+    pub fn lookup_file(&self, pos: usize) -> Option<FileId> {
+        // `self.files` is sorted by `base`, so the predicate is monotonic.
+        let idx = self
+            .files
+            .partition_point(|f| f.base <= pos)
+            .checked_sub(1)?;
+        if self.files[idx].contains(pos) {
+            Some(FileId(idx))
+        } else {
+            None
+        }
+    }
+
+    /// resolves the global offset `pos` to `(file, line, col)`,
+    /// with the column counted in bytes (see [`LineCache::run`]).
+    pub fn resolve(&self, pos: usize) -> Option<(FileId, usize, usize)> {
+        let id = self.lookup_file(pos)?;
+        let f = &self.files[id.0];
+        let (line, col) = f.lines.run(pos - f.base);
+        Some((id, line, col))
+    }
+
+    /// like [`resolve`](Self::resolve), but counts the column in the given `unit`.
+    pub fn resolve_with(&self, pos: usize, unit: ColumnUnit) -> Option<(FileId, usize, usize)> {
+        let id = self.lookup_file(pos)?;
+        let f = &self.files[id.0];
+        let (line, col) = f.lines.run_with(&f.src, pos - f.base, unit);
+        Some((id, line, col))
+    }
+
+    /// the inverse of [`resolve`](Self::resolve): returns the global offset
+    /// of the zero-based (line, col) position in the given file.
+    pub fn offset(&self, id: FileId, line: usize, col: usize) -> Option<usize> {
+        let f = self.files.get(id.0)?;
+        Some(f.base + f.lines.offset(line, col)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_global_offsets() {
+        let mut sm = SourceMap::new();
+        let a = sm.add_file("a.rs", "fn a() {}\n");
+        let b = sm.add_file("b.rs", "fn b() {\n    ä\n}");
+        let e = sm.add_file("empty.rs", "");
+        assert_eq!(sm.file(a).base(), 0);
+        assert_eq!(sm.file(b).base(), 11);
+        assert_eq!(sm.file(e).base(), 29);
+        assert_eq!(sm.file(b).name(), "b.rs");
+
+        assert_eq!(sm.resolve(3), Some((a, 0, 3)));
+        // the end of a file is part of it, the next offset starts the next file
+        assert_eq!(sm.resolve(10), Some((a, 1, 0)));
+        assert_eq!(sm.lookup_file(11), Some(b));
+        assert_eq!(sm.resolve(26), Some((b, 1, 6)));
+        assert_eq!(sm.resolve_with(26, ColumnUnit::Char), Some((b, 1, 5)));
+        assert_eq!(sm.resolve(29), Some((e, 0, 0)));
+        assert_eq!(sm.resolve(30), None);
+
+        // every offset up to the end of the last file belongs to a file
+        for pos in 0..30 {
+            let (id, line, col) = sm.resolve(pos).unwrap();
+            assert_eq!(sm.offset(id, line, col), Some(pos));
+        }
+        assert_eq!(
+            sm.files().map(|(_, f)| f.name()).collect::<Vec<_>>(),
+            ["a.rs", "b.rs", "empty.rs"]
+        );
+    }
+}
@@ -0,0 +1,195 @@
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+use super::{Mapping, Mappings, Original, Remap};
+use alloc::vec::Vec;
+
+/// A generated position, whose original position in the intermediate file
+/// isn't mapped by the earlier map.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Gap {
+    pub generated_line: u32,
+    pub generated_column: u32,
+    /// the position in the intermediate file
+    pub line: u32,
+    pub column: u32,
+}
+
+/// The result of [`Mappings::compose`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Composed {
+    pub mappings: Mappings,
+    /// the generated positions which couldn't be traced through the
+    /// earlier map, in order; they are mapped to `None`
+    pub gaps: Vec<Gap>,
+}
+
+impl Mappings {
+    /// composes these mappings with the `earlier` mappings of their
+    /// intermediate `source`, so that positions are mapped directly to the
+    /// sources of `earlier`. Mappings into other sources are kept.
+    ///
+    /// Each mapping is traced through the mapping of `earlier` which applies
+    /// to its original position. Where there is none, the position maps
+    /// to `None`, rather than to whatever precedes it, and is reported as
+    /// a [`Gap`]. Names of `earlier` take precedence.
+    ///
+    /// Longer chains are composed step by step, starting at the final
+    /// artifact:
+    ///
+    /// ```
+    /// # use linetrack::srcmap_v3::{Mappings, Original};
+    /// // app.dsl -> app.ts -> app.js -> app.min.js
+    /// let mut ts = Mappings::new();
+    /// let dsl = ts.add_source("app.dsl");
+    /// ts.add(5, 4, Some(Original::new(dsl, 1, 0)));
+    /// let mut js = Mappings::new();
+    /// let app_ts = js.add_source("app.ts");
+    /// js.add(3, 0, Some(Original::new(app_ts, 5, 4)));
+    /// let mut min = Mappings::new();
+    /// let app_js = min.add_source("app.js");
+    /// min.add(0, 0, Some(Original::new(app_js, 3, 2)));
+    ///
+    /// let composed = min.compose("app.js", &js).mappings;
+    /// let composed = composed.compose("app.ts", &ts).mappings;
+    /// assert_eq!(composed.sources(), ["app.dsl"]);
+    /// assert_eq!(composed.lookup(0, 0).unwrap().original, Some(Original::new(0, 1, 0)));
+    /// ```
+    pub fn compose(&self, source: &str, earlier: &Mappings) -> Composed {
+        let through = self.sources.iter().position(|s| s == source);
+        let mut out = Mappings::new();
+        let mut gaps = Vec::new();
+        let (mut sources, mut names) = (Remap::new(&self.sources), Remap::new(&self.names));
+        let mut earlier_sources = Remap::new(&earlier.sources);
+        let mut earlier_names = Remap::new(&earlier.names);
+        for m in &self.mappings {
+            let original = match m.original {
+                Some(o) if through == Some(o.source as usize) => {
+                    match earlier.lookup(o.line, o.column).and_then(|e| e.original) {
+                        Some(e) => Some(Original {
+                            source: earlier_sources.get(
+                                e.source,
+                                &earlier.sources,
+                                &mut out.sources,
+                            ),
+                            line: e.line,
+                            column: e.column,
+                            name: match (e.name, o.name) {
+                                (Some(n), _) => {
+                                    Some(earlier_names.get(n, &earlier.names, &mut out.names))
+                                }
+                                (None, n) => n.map(|n| names.get(n, &self.names, &mut out.names)),
+                            },
+                        }),
+                        None => {
+                            gaps.push(Gap {
+                                generated_line: m.generated_line,
+                                generated_column: m.generated_column,
+                                line: o.line,
+                                column: o.column,
+                            });
+                            None
+                        }
+                    }
+                }
+                Some(o) => Some(Original {
+                    source: sources.get(o.source, &self.sources, &mut out.sources),
+                    name: o.name.map(|n| names.get(n, &self.names, &mut out.names)),
+                    ..o
+                }),
+                None => None,
+            };
+            // already in order
+            out.mappings.push(Mapping { original, ..*m });
+        }
+        Composed {
+            mappings: out,
+            gaps,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain() {
+        // app.dsl -> app.ts
+        let mut ts = Mappings::new();
+        let dsl = ts.add_source("app.dsl");
+        let rule = ts.add_name("rule");
+        ts.add(0, 0, Some(Original::new(dsl, 0, 0).with_name(rule)));
+        ts.add(0, 8, None);
+        ts.add(2, 4, Some(Original::new(dsl, 3, 2)));
+
+        // app.ts (and a library) -> app.js
+        let mut js = Mappings::new();
+        let lib = js.add_source("lib.js");
+        let app = js.add_source("app.ts");
+        let f = js.add_name("f");
+        js.add(0, 0, Some(Original::new(lib, 5, 0)));
+        js.add(0, 3, Some(Original::new(app, 0, 2).with_name(f)));
+        js.add(0, 7, Some(Original::new(app, 0, 9)));
+        js.add(0, 9, Some(Original::new(app, 1, 0)));
+        js.add(1, 0, Some(Original::new(app, 2, 6)));
+
+        let Composed { mappings, gaps } = js.compose("app.ts", &ts);
+        assert_eq!(mappings.sources(), ["lib.js", "app.dsl"]);
+        assert_eq!(mappings.names(), ["rule"]);
+        let originals: Vec<_> = mappings.mappings().iter().map(|m| m.original).collect();
+        assert_eq!(
+            originals,
+            [
+                Some(Original::new(0, 5, 0)),
+                Some(Original::new(1, 0, 0).with_name(0)),
+                None,
+                None,
+                Some(Original::new(1, 3, 2)),
+            ]
+        );
+        // mapped to `None` in app.ts, and not mapped at all
+        assert_eq!(
+            gaps,
+            [
+                Gap {
+                    generated_line: 0,
+                    generated_column: 7,
+                    line: 0,
+                    column: 9
+                },
+                Gap {
+                    generated_line: 0,
+                    generated_column: 9,
+                    line: 1,
+                    column: 0
+                },
+            ]
+        );
+        // a gap isn't covered by the preceding mapping
+        assert_eq!(mappings.lookup(0, 8).unwrap().original, None);
+
+        // the outer name is kept where the earlier map has none, and
+        // another step can follow
+        let mut dsl_map = Mappings::new();
+        let spec = dsl_map.add_source("spec.txt");
+        dsl_map.add(0, 0, Some(Original::new(spec, 10, 0)));
+        dsl_map.add(3, 0, Some(Original::new(spec, 20, 0)));
+        let mut js = Mappings::new();
+        let app = js.add_source("app.ts");
+        let g = js.add_name("g");
+        js.add(0, 0, Some(Original::new(app, 2, 5).with_name(g)));
+        let composed = js.compose("app.ts", &ts).mappings;
+        assert_eq!(
+            composed.mappings()[0].original,
+            Some(Original::new(0, 3, 2).with_name(0))
+        );
+        assert_eq!(composed.names(), ["g"]);
+        let composed = composed.compose("app.dsl", &dsl_map);
+        assert_eq!(composed.mappings.sources(), ["spec.txt"]);
+        assert_eq!(
+            composed.mappings.mappings()[0].original,
+            Some(Original::new(0, 20, 0).with_name(0))
+        );
+        assert!(composed.gaps.is_empty());
+    }
+}
@@ -0,0 +1,280 @@
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+use super::{check_version, invalid, json, InvalidSourceMap, Mapping, Mappings, Original, Remap};
+use crate::{ColumnUnit, LineCache};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+/// A section of an [`IndexMap`], whose map applies from a position of the
+/// generated file up to the next section.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Section {
+    pub line: u32,
+    /// only shifts the first line of the section
+    pub column: u32,
+    pub map: Mappings,
+}
+
+/// An index source map, which combines the maps of concatenated files
+/// into sections.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IndexMap {
+    /// sorted by their positions
+    sections: Vec<Section>,
+}
+
+impl IndexMap {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// adds a section starting at the generated position; sections may be
+    /// added in any order.
+    pub fn add_section(&mut self, line: u32, column: u32, map: Mappings) {
+        let idx = self
+            .sections
+            .partition_point(|s| (s.line, s.column) <= (line, column));
+        self.sections.insert(idx, Section { line, column, map });
+    }
+
+    /// returns the sections, ordered by their positions
+    #[inline(always)]
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    /// returns the section which contains the generated position
+    pub fn section(&self, line: u32, column: u32) -> Option<&Section> {
+        let idx = self
+            .sections
+            .partition_point(|s| (s.line, s.column) <= (line, column));
+        self.sections[..idx].last()
+    }
+
+    /// returns the mapping which applies to the generated position, together
+    /// with its section; the generated position of the mapping is relative
+    /// to the section.
+    pub fn lookup(&self, line: u32, column: u32) -> Option<(&Section, &Mapping)> {
+        let section = self.section(line, column)?;
+        let line = line - section.line;
+        let column = match line {
+            0 => column - section.column,
+            _ => column,
+        };
+        Some((section, section.map.lookup(line, column)?))
+    }
+
+    /// like [`lookup`](Self::lookup), but for a byte offset of the generated
+    /// code `src` with the line cache `lines`; columns are counted in
+    /// UTF-16 code units.
+    pub fn lookup_offset(
+        &self,
+        src: &str,
+        lines: &LineCache,
+        offset: usize,
+    ) -> Option<(&Section, &Mapping)> {
+        let (line, column) = lines.run_with(src, offset, ColumnUnit::Utf16);
+        self.lookup(u32::try_from(line).ok()?, u32::try_from(column).ok()?)
+    }
+
+    /// merges the sections into a single map; mappings reaching into the
+    /// next section are dropped.
+    pub fn flatten(&self) -> Mappings {
+        let mut out = Mappings::new();
+        for (i, section) in self.sections.iter().enumerate() {
+            let end = self.sections.get(i + 1).map(|s| (s.line, s.column));
+            let map = &section.map;
+            let (mut sources, mut names) = (Remap::new(&map.sources), Remap::new(&map.names));
+            for m in map.mappings() {
+                let line = section.line.saturating_add(m.generated_line);
+                let column = match m.generated_line {
+                    0 => section.column.saturating_add(m.generated_column),
+                    _ => m.generated_column,
+                };
+                if end.is_some_and(|end| (line, column) >= end) {
+                    break;
+                }
+                let original = m.original.map(|o| Original {
+                    source: sources.get(o.source, &map.sources, &mut out.sources),
+                    name: o.name.map(|n| names.get(n, &map.names, &mut out.names)),
+                    ..o
+                });
+                // already in order
+                out.mappings.push(Mapping {
+                    generated_line: line,
+                    generated_column: column,
+                    original,
+                });
+            }
+        }
+        out
+    }
+
+    /// returns a complete index map file, as JSON
+    pub fn to_json(&self, file: Option<&str>) -> String {
+        let mut out = String::from("{\"version\":3");
+        if let Some(file) = file {
+            out.push_str(",\"file\":");
+            super::json_string(&mut out, file);
+        }
+        out.push_str(",\"sections\":[");
+        for (i, s) in self.sections.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            // writing into a `String` can't fail
+            let _ = write!(
+                out,
+                "{{\"offset\":{{\"line\":{},\"column\":{}}},\"map\":{}}}",
+                s.line,
+                s.column,
+                s.map.to_json(None)
+            );
+        }
+        out.push_str("]}");
+        out
+    }
+
+    /// reads an index map file; sections referring to their map by `url`
+    /// aren't supported.
+    pub fn from_json(json: &str) -> Result<Self, InvalidSourceMap> {
+        let value = json::parse(json).map_err(|offset| InvalidSourceMap { offset })?;
+        check_version(&value)?;
+        let sections = value.get("sections").ok_or(invalid(&value))?;
+        let json::Kind::Array(items) = &sections.kind else {
+            return Err(invalid(sections));
+        };
+        let mut out = Self::new();
+        for item in items {
+            let offset = item.get("offset").ok_or(invalid(item))?;
+            let map = item.get("map").ok_or(invalid(item))?;
+            out.add_section(
+                number(offset.get("line").ok_or(invalid(offset))?)?,
+                number(offset.get("column").ok_or(invalid(offset))?)?,
+                Mappings::from_value(map)?,
+            );
+        }
+        Ok(out)
+    }
+}
+
+fn number(value: &json::Value) -> Result<u32, InvalidSourceMap> {
+    match value.kind {
+        json::Kind::Number(Some(n)) => u32::try_from(n).map_err(|_| invalid(value)),
+        _ => Err(invalid(value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `a();b();` followed by the second line of `b.js`
+    fn bundle() -> IndexMap {
+        let mut a = Mappings::new();
+        let src = a.add_source("a.js");
+        let name = a.add_name("a");
+        a.add(0, 0, Some(Original::new(src, 0, 0).with_name(name)));
+        // reaches into the next section
+        a.add(0, 5, Some(Original::new(src, 0, 5)));
+
+        let mut b = Mappings::new();
+        let src = b.add_source("b.js");
+        b.add(0, 0, Some(Original::new(src, 3, 2)));
+        b.add(1, 2, Some(Original::new(src, 4, 0)));
+
+        let mut map = IndexMap::new();
+        map.add_section(0, 4, b);
+        map.add_section(0, 0, a);
+        map
+    }
+
+    #[test]
+    fn lookup() {
+        let map = bundle();
+        let at = |line, column| {
+            map.lookup(line, column).map(|(s, m)| {
+                let o = m.original.unwrap();
+                (
+                    s.map.sources()[o.source as usize].as_str(),
+                    o.line,
+                    o.column,
+                )
+            })
+        };
+        assert_eq!(at(0, 3), Some(("a.js", 0, 0)));
+        assert_eq!(at(0, 6), Some(("b.js", 3, 2)));
+        // not shifted by the column of the section
+        assert_eq!(at(1, 1), None);
+        assert_eq!(at(1, 2), Some(("b.js", 4, 0)));
+
+        let src = "a();b();\n  c();\n";
+        let lines = LineCache::new(src);
+        let (section, _) = map
+            .lookup_offset(src, &lines, src.find('c').unwrap())
+            .unwrap();
+        assert_eq!(section.line, 0);
+        assert_eq!(section.column, 4);
+        assert_eq!(IndexMap::new().lookup(0, 0), None);
+
+        let flat = map.flatten();
+        assert_eq!(flat.sources(), ["a.js", "b.js"]);
+        assert_eq!(flat.encode(), "AAAAA,ICGE;EACF");
+        assert_eq!(
+            flat.lookup(1, 2).and_then(|m| m.original),
+            Some(Original::new(1, 4, 0))
+        );
+    }
+
+    #[test]
+    fn json() {
+        let map = bundle();
+        let json = map.to_json(Some("bundle.js"));
+        assert_eq!(
+            json,
+            "{\"version\":3,\"file\":\"bundle.js\",\"sections\":[\
+             {\"offset\":{\"line\":0,\"column\":0},\"map\":{\"version\":3,\
+             \"sources\":[\"a.js\"],\"names\":[\"a\"],\"mappings\":\"AAAAA,KAAK\"}},\
+             {\"offset\":{\"line\":0,\"column\":4},\"map\":{\"version\":3,\
+             \"sources\":[\"b.js\"],\"names\":[],\"mappings\":\"AAGE;EACF\"}}]}"
+        );
+        assert_eq!(IndexMap::from_json(&json), Ok(map));
+
+        let section = |offset: &str, map: &str| {
+            IndexMap::from_json(&alloc::format!(
+                "{{\"version\":3,\"sections\":[{{\"offset\":{},\"map\":{}}}]}}",
+                offset,
+                map
+            ))
+        };
+        let map = "{\"version\": 3, \"sourceRoot\": \"src/\", \"sources\": [null, \"b.js\"], \"mappings\": \"ACAA\"}";
+        let index = section("{\"line\": 1, \"column\": 0}", map).unwrap();
+        assert_eq!(index.sections()[0].map.sources(), ["", "src/b.js"]);
+
+        assert_eq!(
+            IndexMap::from_json("{\"version\":3}"),
+            Err(InvalidSourceMap { offset: 0 })
+        );
+        assert_eq!(
+            IndexMap::from_json("{\"version\":2,\"sections\":[]}"),
+            Err(InvalidSourceMap { offset: 11 })
+        );
+        assert_eq!(
+            IndexMap::from_json("{\"version\":3,\"sections\":[}"),
+            Err(InvalidSourceMap { offset: 25 })
+        );
+        // the negative line
+        assert_eq!(
+            section("{\"line\": -1, \"column\": 0}", map),
+            Err(InvalidSourceMap { offset: 44 })
+        );
+        assert!(section(
+            "{\"line\": 0, \"column\": 0}",
+            "{\"version\": 3, \"mappings\": \"AC\"}"
+        )
+        .is_err());
+        assert!(section("{\"line\": 0}", map).is_err());
+    }
+}
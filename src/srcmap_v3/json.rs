@@ -0,0 +1,311 @@
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+//! A minimal JSON parser, just enough to read source maps.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// the maximum nesting of arrays and objects, which keeps malicious input
+/// from overflowing the stack
+const MAX_DEPTH: usize = 64;
+
+/// A parsed value, together with its byte offset for error reporting.
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct Value {
+    pub(super) offset: usize,
+    pub(super) kind: Kind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(super) enum Kind {
+    Null,
+    Bool(bool),
+    /// `None` if the number isn't an integer which fits into an `i64`
+    Number(Option<i64>),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// returns the member `key` of an object
+    pub(super) fn get(&self, key: &str) -> Option<&Value> {
+        match &self.kind {
+            Kind::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+/// parses a JSON document, or returns the byte offset of the syntax error
+pub(super) fn parse(json: &str) -> Result<Value, usize> {
+    let mut p = Parser { src: json, pos: 0 };
+    let value = p.value(0)?;
+    p.skip_ws();
+    if p.pos != json.len() {
+        return Err(p.pos);
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    #[inline]
+    fn peek(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_ws(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    /// skips whitespace, and `c` if it follows
+    fn eat(&mut self, c: u8) -> bool {
+        self.skip_ws();
+        let found = self.peek() == Some(c);
+        self.pos += usize::from(found);
+        found
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, usize> {
+        self.skip_ws();
+        let offset = self.pos;
+        let kind = match self.peek() {
+            Some(b'{' | b'[') if depth == MAX_DEPTH => return Err(offset),
+            Some(b'{') => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                if !self.eat(b'}') {
+                    loop {
+                        self.skip_ws();
+                        let key = self.string()?;
+                        if !self.eat(b':') {
+                            return Err(self.pos);
+                        }
+                        members.push((key, self.value(depth + 1)?));
+                        if self.eat(b'}') {
+                            break;
+                        }
+                        if !self.eat(b',') {
+                            return Err(self.pos);
+                        }
+                    }
+                }
+                Kind::Object(members)
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if !self.eat(b']') {
+                    loop {
+                        items.push(self.value(depth + 1)?);
+                        if self.eat(b']') {
+                            break;
+                        }
+                        if !self.eat(b',') {
+                            return Err(self.pos);
+                        }
+                    }
+                }
+                Kind::Array(items)
+            }
+            Some(b'"') => Kind::String(self.string()?),
+            Some(b't') => self.literal("true", Kind::Bool(true))?,
+            Some(b'f') => self.literal("false", Kind::Bool(false))?,
+            Some(b'n') => self.literal("null", Kind::Null)?,
+            Some(b'-' | b'0'..=b'9') => self.number()?,
+            _ => return Err(offset),
+        };
+        Ok(Value { offset, kind })
+    }
+
+    fn literal(&mut self, word: &str, kind: Kind) -> Result<Kind, usize> {
+        if !self.src[self.pos..].starts_with(word) {
+            return Err(self.pos);
+        }
+        self.pos += word.len();
+        Ok(kind)
+    }
+
+    /// skips digits, returning how many there were
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    fn number(&mut self) -> Result<Kind, usize> {
+        let start = self.pos;
+        self.pos += usize::from(self.peek() == Some(b'-'));
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => {
+                self.digits();
+            }
+            _ => return Err(self.pos),
+        }
+        let mut integer = true;
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            integer = false;
+            if self.digits() == 0 {
+                return Err(self.pos);
+            }
+        }
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            self.pos += 1;
+            integer = false;
+            self.pos += usize::from(matches!(self.peek(), Some(b'+' | b'-')));
+            if self.digits() == 0 {
+                return Err(self.pos);
+            }
+        }
+        let value = self.src[start..self.pos].parse().ok();
+        Ok(Kind::Number(value.filter(|_| integer)))
+    }
+
+    fn string(&mut self) -> Result<String, usize> {
+        if self.peek() != Some(b'"') {
+            return Err(self.pos);
+        }
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let start = self.pos;
+            while self
+                .peek()
+                .is_some_and(|c| c != b'"' && c != b'\\' && c >= 0x20)
+            {
+                self.pos += 1;
+            }
+            // only stops at ASCII, so this is a `char` boundary
+            out.push_str(&self.src[start..self.pos]);
+            let escape = self.pos + 1;
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => self.pos += 2,
+                _ => return Err(self.pos),
+            }
+            let c = match self.src.as_bytes().get(escape) {
+                Some(b'"') => '"',
+                Some(b'\\') => '\\',
+                Some(b'/') => '/',
+                Some(b'b') => '\u{8}',
+                Some(b'f') => '\u{c}',
+                Some(b'n') => '\n',
+                Some(b'r') => '\r',
+                Some(b't') => '\t',
+                Some(b'u') => {
+                    let mut c = self.hex4()?;
+                    if (0xD800..0xDC00).contains(&c) && self.src[self.pos..].starts_with("\\u") {
+                        self.pos += 2;
+                        let low = self.hex4()?;
+                        if !(0xDC00..0xE000).contains(&low) {
+                            return Err(self.pos - 6);
+                        }
+                        c = 0x10000 + ((c - 0xD800) << 10) + (low - 0xDC00);
+                    }
+                    char::from_u32(c).ok_or(escape - 1)?
+                }
+                _ => return Err(escape - 1),
+            };
+            out.push(c);
+        }
+    }
+
+    /// parses the four hex digits of a `\u` escape
+    fn hex4(&mut self) -> Result<u32, usize> {
+        let hex = self.src.get(self.pos..self.pos + 4).ok_or(self.pos)?;
+        if !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return Err(self.pos);
+        }
+        self.pos += 4;
+        u32::from_str_radix(hex, 16).map_err(|_| self.pos - 4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn kind(json: &str) -> Result<Kind, usize> {
+        parse(json).map(|v| v.kind)
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(
+            kind(" [1, -20, 1.5, 2e3, true, null, \"a\\n\\u00e4\\ud83d\\ude00\"] "),
+            Ok(Kind::Array(vec![
+                Value {
+                    offset: 2,
+                    kind: Kind::Number(Some(1))
+                },
+                Value {
+                    offset: 5,
+                    kind: Kind::Number(Some(-20))
+                },
+                Value {
+                    offset: 10,
+                    kind: Kind::Number(None)
+                },
+                Value {
+                    offset: 15,
+                    kind: Kind::Number(None)
+                },
+                Value {
+                    offset: 20,
+                    kind: Kind::Bool(true)
+                },
+                Value {
+                    offset: 26,
+                    kind: Kind::Null
+                },
+                Value {
+                    offset: 32,
+                    kind: Kind::String("a\nä😀".into())
+                },
+            ]))
+        );
+        let obj = parse("{\"a\": {\"b\": []}, \"ü\": \"ö\"}").unwrap();
+        assert_eq!(
+            obj.get("a").and_then(|a| a.get("b")).map(|b| b.offset),
+            Some(12)
+        );
+        assert_eq!(
+            obj.get("ü").map(|v| &v.kind),
+            Some(&Kind::String("ö".into()))
+        );
+        assert_eq!(obj.get("c"), None);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(kind(""), Err(0));
+        assert_eq!(kind("[1,]"), Err(3));
+        assert_eq!(kind("{\"a\" 1}"), Err(5));
+        assert_eq!(kind("{a: 1}"), Err(1));
+        assert_eq!(kind("01"), Err(1));
+        assert_eq!(kind("1."), Err(2));
+        assert_eq!(kind("\"a\\x\""), Err(2));
+        assert_eq!(kind("\"\\ud800\\u0041\""), Err(7));
+        assert_eq!(kind("\"a\nb\""), Err(2));
+        assert_eq!(kind("\"abc"), Err(4));
+        assert_eq!(kind("[] x"), Err(3));
+        assert_eq!(kind("tru"), Err(0));
+        let deep = "[".repeat(100);
+        assert_eq!(kind(&deep), Err(MAX_DEPTH));
+    }
+}
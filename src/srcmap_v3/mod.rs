@@ -0,0 +1,560 @@
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+//! Mappings of [Source Map v3](https://tc39.es/ecma426/) files, and index
+//! maps which combine them into sections.
+//!
+//! Not to be confused with the [`SourceMap`](crate::SourceMap) registry
+//! of source files. Lines and columns are zero-based; columns are usually
+//! counted in UTF-16 code units, as JavaScript does.
+//!
+//! ```
+//! use linetrack::srcmap_v3::{Mappings, Original};
+//!
+//! let mut map = Mappings::new();
+//! let src = map.add_source("main.ts");
+//! let name = map.add_name("greet");
+//! map.add(0, 0, Some(Original::new(src, 2, 4)));
+//! map.add(0, 9, Some(Original::new(src, 2, 13).with_name(name)));
+//! map.add(1, 0, None);
+//! assert_eq!(map.encode(), "AAEI,SAASA;A");
+//!
+//! let sources = map.sources().to_vec();
+//! let decoded = Mappings::decode("AAEI,SAASA;A", sources, map.names().to_vec()).unwrap();
+//! let original = decoded.lookup(0, 12).and_then(|m| m.original);
+//! assert_eq!(original, Some(Original::new(src, 2, 13).with_name(name)));
+//! ```
+
+mod compose;
+mod indexed;
+mod json;
+mod vlq;
+
+pub use compose::{Composed, Gap};
+pub use indexed::{IndexMap, Section};
+
+use crate::{ColumnUnit, LineCache};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+/// The original position a generated position comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Original {
+    /// the index into the sources of the map
+    pub source: u32,
+    pub line: u32,
+    pub column: u32,
+    /// the index into the names of the map
+    pub name: Option<u32>,
+}
+
+impl Original {
+    #[inline]
+    pub const fn new(source: u32, line: u32, column: u32) -> Self {
+        Self {
+            source,
+            line,
+            column,
+            name: None,
+        }
+    }
+
+    #[inline]
+    pub const fn with_name(self, name: u32) -> Self {
+        Self {
+            name: Some(name),
+            ..self
+        }
+    }
+}
+
+/// A single mapping, which applies from its generated position up to the
+/// next mapping in the same line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Mapping {
+    pub generated_line: u32,
+    pub generated_column: u32,
+    /// `None` marks generated code which doesn't come from any source
+    pub original: Option<Original>,
+}
+
+/// The error returned when decoding invalid mappings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidMappings {
+    /// the byte offset of the invalid segment in the mappings string
+    pub offset: usize,
+}
+
+impl fmt::Display for InvalidMappings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid source map mappings at offset {}", self.offset)
+    }
+}
+
+impl core::error::Error for InvalidMappings {}
+
+/// The error returned when reading an invalid source map file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidSourceMap {
+    /// the byte offset of the invalid JSON value
+    pub offset: usize,
+}
+
+impl fmt::Display for InvalidSourceMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid source map at offset {}", self.offset)
+    }
+}
+
+impl core::error::Error for InvalidSourceMap {}
+
+/// The mappings of a source map, together with its sources and names.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Mappings {
+    sources: Vec<String>,
+    names: Vec<String>,
+    /// sorted by their generated positions
+    mappings: Vec<Mapping>,
+}
+
+impl Mappings {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// adds a source file name, or returns the index of it if it was
+    /// added before
+    pub fn add_source(&mut self, name: &str) -> u32 {
+        intern(&mut self.sources, name)
+    }
+
+    /// adds a symbol name, or returns the index of it if it was added before
+    pub fn add_name(&mut self, name: &str) -> u32 {
+        intern(&mut self.names, name)
+    }
+
+    /// adds a mapping from the generated position to `original`; mappings
+    /// may be added in any order.
+    ///
+    /// # Panics
+    /// Panics if the source or name index of `original` is out of range.
+    pub fn add(&mut self, generated_line: u32, generated_column: u32, original: Option<Original>) {
+        if let Some(o) = original {
+            assert!(o.source < self.sources.len() as u32, "source out of range");
+            assert!(
+                o.name.is_none_or(|n| n < self.names.len() as u32),
+                "name out of range"
+            );
+        }
+        let key = (generated_line, generated_column);
+        let idx = self
+            .mappings
+            .partition_point(|m| (m.generated_line, m.generated_column) <= key);
+        self.mappings.insert(
+            idx,
+            Mapping {
+                generated_line,
+                generated_column,
+                original,
+            },
+        );
+    }
+
+    #[inline(always)]
+    pub fn sources(&self) -> &[String] {
+        &self.sources
+    }
+
+    #[inline(always)]
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// returns the mappings, ordered by their generated positions
+    #[inline(always)]
+    pub fn mappings(&self) -> &[Mapping] {
+        &self.mappings
+    }
+
+    /// returns the mapping which applies to the generated position, i.e. the
+    /// last one at or before it in the same line
+    pub fn lookup(&self, line: u32, column: u32) -> Option<&Mapping> {
+        let idx = self
+            .mappings
+            .partition_point(|m| (m.generated_line, m.generated_column) <= (line, column));
+        self.mappings[..idx]
+            .last()
+            .filter(|m| m.generated_line == line)
+    }
+
+    /// like [`lookup`](Self::lookup), but for a byte offset of the generated
+    /// code `src` with the line cache `lines`; columns are counted in
+    /// UTF-16 code units.
+    pub fn lookup_offset(&self, src: &str, lines: &LineCache, offset: usize) -> Option<&Mapping> {
+        let (line, column) = lines.run_with(src, offset, ColumnUnit::Utf16);
+        self.lookup(u32::try_from(line).ok()?, u32::try_from(column).ok()?)
+    }
+
+    /// returns the VLQ-encoded `mappings` string
+    pub fn encode(&self) -> String {
+        let mut out = String::new();
+        let mut line = 0;
+        // the previous values of the fields, which are encoded relative
+        let mut column = 0i64;
+        let (mut source, mut orig_line, mut orig_column, mut name) = (0i64, 0i64, 0i64, 0i64);
+        for (i, m) in self.mappings.iter().enumerate() {
+            if m.generated_line > line {
+                for _ in line..m.generated_line {
+                    out.push(';');
+                }
+                line = m.generated_line;
+                column = 0;
+            } else if i > 0 {
+                out.push(',');
+            }
+            let mut field = |value: u32, prev: &mut i64| {
+                vlq::encode(i64::from(value) - *prev, &mut out);
+                *prev = i64::from(value);
+            };
+            field(m.generated_column, &mut column);
+            if let Some(o) = m.original {
+                field(o.source, &mut source);
+                field(o.line, &mut orig_line);
+                field(o.column, &mut orig_column);
+                if let Some(n) = o.name {
+                    field(n, &mut name);
+                }
+            }
+        }
+        out
+    }
+
+    /// decodes a `mappings` string, given the `sources` and `names`
+    /// of the map
+    pub fn decode(
+        mappings: &str,
+        sources: Vec<String>,
+        names: Vec<String>,
+    ) -> Result<Self, InvalidMappings> {
+        let dat = mappings.as_bytes();
+        let mut out = Vec::new();
+        let mut line = 0u32;
+        let mut column = 0i64;
+        let mut prev = [0i64; 4];
+        let mut pos = 0;
+        while pos < dat.len() {
+            match dat[pos] {
+                b';' => {
+                    line = line.checked_add(1).ok_or(InvalidMappings { offset: pos })?;
+                    column = 0;
+                    pos += 1;
+                    continue;
+                }
+                b',' => {
+                    pos += 1;
+                    continue;
+                }
+                _ => {}
+            }
+            let err = InvalidMappings { offset: pos };
+            let mut fields = [0i64; 5];
+            let mut n = 0;
+            while pos < dat.len() && !matches!(dat[pos], b',' | b';') {
+                let (value, len) = vlq::decode(&dat[pos..]).ok_or(err)?;
+                *fields.get_mut(n).ok_or(err)? = value;
+                n += 1;
+                pos += len;
+            }
+            let absolute = |prev: &mut i64, delta: i64, limit: usize| {
+                let value = prev.checked_add(delta).ok_or(err)?;
+                *prev = value;
+                u32::try_from(value)
+                    .ok()
+                    .filter(|&v| (v as usize) < limit)
+                    .ok_or(err)
+            };
+            let generated_column = absolute(&mut column, fields[0], u32::MAX as usize)?;
+            let original = match n {
+                1 => None,
+                4 | 5 => {
+                    let [source, orig_line, orig_column, name] = &mut prev;
+                    Some(Original {
+                        source: absolute(source, fields[1], sources.len())?,
+                        line: absolute(orig_line, fields[2], u32::MAX as usize)?,
+                        column: absolute(orig_column, fields[3], u32::MAX as usize)?,
+                        name: match n {
+                            5 => Some(absolute(name, fields[4], names.len())?),
+                            _ => None,
+                        },
+                    })
+                }
+                _ => return Err(err),
+            };
+            out.push(Mapping {
+                generated_line: line,
+                generated_column,
+                original,
+            });
+        }
+        // segments are usually sorted by column, but don't have to be
+        out.sort_by_key(|m| (m.generated_line, m.generated_column));
+        Ok(Self {
+            sources,
+            names,
+            mappings: out,
+        })
+    }
+
+    /// returns a complete source map file, as JSON
+    pub fn to_json(&self, file: Option<&str>) -> String {
+        let mut out = String::from("{\"version\":3");
+        if let Some(file) = file {
+            out.push_str(",\"file\":");
+            json_string(&mut out, file);
+        }
+        for (key, list) in [("sources", &self.sources), ("names", &self.names)] {
+            // writing into a `String` can't fail
+            let _ = write!(out, ",\"{}\":[", key);
+            for (i, s) in list.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                json_string(&mut out, s);
+            }
+            out.push(']');
+        }
+        out.push_str(",\"mappings\":\"");
+        out.push_str(&self.encode());
+        out.push_str("\"}");
+        out
+    }
+
+    /// reads a source map file; the `sourceRoot` is prepended to the
+    /// sources, separated by `/` unless it ends with one. Other fields
+    /// like `file` or `sourcesContent` are ignored.
+    pub fn from_json(json: &str) -> Result<Self, InvalidSourceMap> {
+        let value = json::parse(json).map_err(|offset| InvalidSourceMap { offset })?;
+        Self::from_value(&value)
+    }
+
+    fn from_value(value: &json::Value) -> Result<Self, InvalidSourceMap> {
+        check_version(value)?;
+        let root = match value.get("sourceRoot") {
+            Some(json::Value {
+                kind: json::Kind::Null,
+                ..
+            })
+            | None => "",
+            Some(root) => string(root)?,
+        };
+        let mut sources = strings(value, "sources")?;
+        if !root.is_empty() {
+            let sep = if root.ends_with('/') { "" } else { "/" };
+            for source in sources.iter_mut().filter(|s| !s.is_empty()) {
+                source.insert_str(0, sep);
+                source.insert_str(0, root);
+            }
+        }
+        let names = strings(value, "names")?;
+        let mappings = value.get("mappings").ok_or(invalid(value))?;
+        Self::decode(string(mappings)?, sources, names).map_err(|_| invalid(mappings))
+    }
+}
+
+#[inline]
+fn invalid(value: &json::Value) -> InvalidSourceMap {
+    InvalidSourceMap {
+        offset: value.offset,
+    }
+}
+
+/// checks that `value` is an object with `"version": 3`
+fn check_version(value: &json::Value) -> Result<(), InvalidSourceMap> {
+    match value.get("version") {
+        Some(json::Value {
+            kind: json::Kind::Number(Some(3)),
+            ..
+        }) => Ok(()),
+        Some(version) => Err(invalid(version)),
+        None => Err(invalid(value)),
+    }
+}
+
+fn string(value: &json::Value) -> Result<&str, InvalidSourceMap> {
+    match &value.kind {
+        json::Kind::String(s) => Ok(s),
+        _ => Err(invalid(value)),
+    }
+}
+
+/// returns the list of strings `key` of `value`, where `null` entries
+/// become empty strings; a missing list is empty
+fn strings(value: &json::Value, key: &str) -> Result<Vec<String>, InvalidSourceMap> {
+    let Some(list) = value.get(key) else {
+        return Ok(Vec::new());
+    };
+    let json::Kind::Array(items) = &list.kind else {
+        return Err(invalid(list));
+    };
+    items
+        .iter()
+        .map(|item| match &item.kind {
+            json::Kind::Null => Ok(String::new()),
+            _ => string(item).map(String::from),
+        })
+        .collect()
+}
+
+/// returns the index of `name` in `list`, adding it if necessary
+fn intern(list: &mut Vec<String>, name: &str) -> u32 {
+    match list.iter().position(|s| s == name) {
+        Some(idx) => idx as u32,
+        None => {
+            list.push(name.into());
+            (list.len() - 1) as u32
+        }
+    }
+}
+
+/// The indices in the merged map of the sources or names of another map,
+/// which are only interned once they are used.
+struct Remap(Vec<Option<u32>>);
+
+impl Remap {
+    fn new(list: &[String]) -> Self {
+        Self(alloc::vec![None; list.len()])
+    }
+
+    /// returns the index in `out` of `list[idx]`
+    fn get(&mut self, idx: u32, list: &[String], out: &mut Vec<String>) -> u32 {
+        *self.0[idx as usize].get_or_insert_with(|| intern(out, &list[idx as usize]))
+    }
+}
+
+/// appends `s` as JSON string literal
+fn json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if u32::from(c) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn sample() -> Mappings {
+        let mut map = Mappings::new();
+        let a = map.add_source("a.ts");
+        let b = map.add_source("dir/b.ts");
+        assert_eq!(map.add_source("a.ts"), a);
+        let f = map.add_name("f");
+        map.add(2, 0, Some(Original::new(b, 0, 0)));
+        map.add(0, 0, Some(Original::new(a, 0, 0)));
+        map.add(0, 4, Some(Original::new(a, 0, 4).with_name(f)));
+        map.add(0, 10, None);
+        map.add(0, 12, Some(Original::new(a, 1, 2)));
+        map.add(2, 6, Some(Original::new(a, 0, 4).with_name(f)));
+        map
+    }
+
+    #[test]
+    fn encode_decode() {
+        let map = sample();
+        let encoded = map.encode();
+        assert_eq!(encoded, "AAAA,IAAIA,M,EACF;;ACDF,MDAIA");
+        let decoded =
+            Mappings::decode(&encoded, map.sources().to_vec(), map.names().to_vec()).unwrap();
+        assert_eq!(decoded, map);
+        assert_eq!(
+            map.to_json(Some("out.js")),
+            "{\"version\":3,\"file\":\"out.js\",\"sources\":[\"a.ts\",\"dir/b.ts\"],\
+             \"names\":[\"f\"],\"mappings\":\"AAAA,IAAIA,M,EACF;;ACDF,MDAIA\"}"
+        );
+        assert_eq!(Mappings::from_json(&map.to_json(None)), Ok(map));
+        // the name is out of range
+        assert_eq!(
+            Mappings::from_json("{\"version\":3,\"sources\":[\"a\"],\"mappings\":\"AAAAA\"}"),
+            Err(InvalidSourceMap { offset: 40 })
+        );
+    }
+
+    #[test]
+    fn source_root() {
+        let sources = |root: &str| {
+            let json = alloc::format!(
+                "{{\"version\":3,\"sourceRoot\":{},\"sources\":[\"a.ts\",null],\"mappings\":\"\"}}",
+                root
+            );
+            Mappings::from_json(&json).map(|m| m.sources)
+        };
+        assert_eq!(
+            sources("\"src\""),
+            Ok(vec!["src/a.ts".into(), String::new()])
+        );
+        assert_eq!(
+            sources("\"src/\""),
+            Ok(vec!["src/a.ts".into(), String::new()])
+        );
+        assert_eq!(sources("\"\""), Ok(vec!["a.ts".into(), String::new()]));
+        assert_eq!(sources("null"), Ok(vec!["a.ts".into(), String::new()]));
+        assert_eq!(sources("1"), Err(InvalidSourceMap { offset: 26 }));
+    }
+
+    #[test]
+    fn invalid() {
+        let sources = || vec![String::from("a.ts")];
+        let decode = |s: &str| Mappings::decode(s, sources(), Vec::new()).map(|m| m.mappings.len());
+        assert_eq!(decode(";;A,,;"), Ok(1));
+        // two or three fields
+        assert_eq!(decode("AAAA,AA"), Err(InvalidMappings { offset: 5 }));
+        assert_eq!(decode("AAA"), Err(InvalidMappings { offset: 0 }));
+        // source or name out of range
+        assert_eq!(decode("ACAA"), Err(InvalidMappings { offset: 0 }));
+        assert_eq!(decode("AAAAA"), Err(InvalidMappings { offset: 0 }));
+        // negative column, incomplete value, invalid character
+        assert_eq!(decode("D"), Err(InvalidMappings { offset: 0 }));
+        assert_eq!(decode("Ag"), Err(InvalidMappings { offset: 0 }));
+        assert_eq!(decode("A!"), Err(InvalidMappings { offset: 0 }));
+    }
+
+    #[test]
+    fn lookup() {
+        let map = sample();
+        let at = |line, column| map.lookup(line, column).map(|m| m.original);
+        assert_eq!(at(0, 3), Some(Some(Original::new(0, 0, 0))));
+        assert_eq!(at(0, 4).unwrap().unwrap().name, Some(0));
+        assert_eq!(at(0, 11), Some(None));
+        assert_eq!(at(0, 100), Some(Some(Original::new(0, 1, 2))));
+        assert_eq!(at(1, 0), None);
+        assert_eq!(at(2, 5), Some(Some(Original::new(1, 0, 0))));
+
+        // offsets are resolved with UTF-16 columns
+        let src = "let 😀 = f(x);\n\nfoo();g\n";
+        let lines = LineCache::new(src);
+        let f = src.find('f').unwrap();
+        assert_eq!(
+            map.lookup_offset(src, &lines, f)
+                .map(|m| m.generated_column),
+            Some(4)
+        );
+        let g = src.find('g').unwrap();
+        assert_eq!(
+            map.lookup_offset(src, &lines, g).and_then(|m| m.original),
+            Some(Original::new(0, 0, 4).with_name(0))
+        );
+    }
+}
@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+//! Base64 VLQ, as used in the `mappings` of source maps.
+
+use alloc::string::String;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const CONTINUATION: u8 = 0b10_0000;
+
+/// appends the VLQ encoding of `value` to `out`
+pub(super) fn encode(value: i64, out: &mut String) {
+    // the sign is stored in the least significant bit
+    let mut v = (value.unsigned_abs() << 1) | u64::from(value < 0);
+    loop {
+        let mut digit = (v & 0b1_1111) as u8;
+        v >>= 5;
+        if v != 0 {
+            digit |= CONTINUATION;
+        }
+        out.push(char::from(ALPHABET[usize::from(digit)]));
+        if v == 0 {
+            break;
+        }
+    }
+}
+
+#[inline]
+fn digit(c: u8) -> Option<u8> {
+    Some(match c {
+        b'A'..=b'Z' => c - b'A',
+        b'a'..=b'z' => c - b'a' + 26,
+        b'0'..=b'9' => c - b'0' + 52,
+        b'+' => 62,
+        b'/' => 63,
+        _ => return None,
+    })
+}
+
+/// decodes a single value from the start of `dat`, returning it together
+/// with the number of bytes it took; `None` if `dat` doesn't start with a
+/// complete value, or it doesn't fit into an `i64`.
+pub(super) fn decode(dat: &[u8]) -> Option<(i64, usize)> {
+    let mut v = 0u64;
+    for (i, &c) in dat.iter().enumerate() {
+        let d = digit(c)?;
+        let part = u64::from(d & 0b1_1111);
+        let shift = 5 * i;
+        // bits shifted out of the `u64` would be lost
+        if shift >= 64 || (part << shift) >> shift != part {
+            return None;
+        }
+        v |= part << shift;
+        if d & CONTINUATION == 0 {
+            let magnitude = i64::try_from(v >> 1).ok()?;
+            let value = if v & 1 == 1 { -magnitude } else { magnitude };
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        for (value, encoded) in [
+            (0, "A"),
+            (1, "C"),
+            (-1, "D"),
+            (15, "e"),
+            (16, "gB"),
+            (-16, "hB"),
+            (123456, "gkxH"),
+        ] {
+            let mut s = String::new();
+            encode(value, &mut s);
+            assert_eq!(s, encoded);
+            assert_eq!(decode(s.as_bytes()), Some((value, s.len())));
+        }
+        for value in [i64::MAX, i64::MIN + 1, -1_000_000_007] {
+            let mut s = String::new();
+            encode(value, &mut s);
+            assert_eq!(decode(s.as_bytes()), Some((value, s.len())));
+        }
+        // incomplete, invalid, or too large
+        assert_eq!(decode(b"g"), None);
+        assert_eq!(decode(b"!"), None);
+        assert_eq!(decode(b"gggggggggggggggA"), None);
+    }
+}
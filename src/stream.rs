@@ -0,0 +1,510 @@
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+use crate::unit::incomplete_len;
+use crate::{ColumnUnit, LineTerminators, Position};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// A position tracker which is fed the input in successive chunks,
+/// e.g. while reading a file or socket, without keeping the whole input.
+///
+/// Line terminators and UTF-8 sequences may be split between chunks.
+/// The tracker follows the same position model as [`LineCache`](crate::LineCache)
+/// (see the [crate documentation](crate)), with one exception: if the input
+/// fed so far ends with a `\r` and the terminators treat a lone `\r` as line
+/// break, it isn't known yet whether a `\n` follows, so the `\r` is counted
+/// like an ordinary byte until more input arrives or [`finish`](Self::finish)
+/// is called.
+///
+/// The end of the input may only be temporary, e.g. while following a growing
+/// log file, so input may still be fed after `finish`. If a `\r` counted as
+/// line break by `finish` is then followed by a `\n`, both are counted as a
+/// single `\r\n`.
+#[derive(Clone, Debug, Default)]
+pub struct PosTrackerStream {
+    offset: usize,
+    line: usize,
+    /// the column after the processed bytes
+    column: usize,
+    unit: ColumnUnit,
+    tab_width: usize,
+    terminators: LineTerminators,
+    /// bytes at the end of the input whose meaning depends on the following
+    /// ones: an incomplete UTF-8 sequence, or a `\r` which may start a `\r\n`
+    held: Vec<u8>,
+    /// the input ended with a `\r` which [`finish`](Self::finish) counted
+    /// as line break, so a following `\n` doesn't start another line
+    finished_cr: bool,
+    /// the processed bytes of the current line,
+    /// only kept if the unit needs them to count columns
+    line_buf: Vec<u8>,
+}
+
+impl PosTrackerStream {
+    /// creates a tracker counting columns in bytes
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// creates a tracker counting columns in `unit`.
+    ///
+    /// `ColumnUnit::Grapheme` needs the whole current line to count
+    /// columns, so the tracker keeps it in that case.
+    #[inline]
+    pub fn with_unit(unit: ColumnUnit) -> Self {
+        Self {
+            unit,
+            ..Self::default()
+        }
+    }
+
+    /// sets the tab width used for column computation,
+    /// the default of 0 disables tab expansion.
+    #[inline]
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width;
+    }
+
+    /// sets the line terminators to recognize,
+    /// the default only treats `\n` as line terminator.
+    #[inline]
+    pub fn set_terminators(&mut self, terminators: LineTerminators) {
+        self.terminators = terminators;
+    }
+
+    /// returns the current offset, the number of bytes fed so far
+    #[inline(always)]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// returns the zero-based current line
+    #[inline(always)]
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// returns the zero-based current column, counted in the configured
+    /// unit with tabs expanded
+    #[inline]
+    pub fn column(&self) -> usize {
+        if self.held.is_empty() {
+            self.column
+        } else {
+            self.unit.advance(&self.held, self.column, self.tab_width)
+        }
+    }
+
+    /// advances the tracker over the next chunk of the input
+    pub fn feed(&mut self, mut chunk: &[u8]) {
+        self.offset += chunk.len();
+        if self.finished_cr && !chunk.is_empty() {
+            self.finished_cr = false;
+            if chunk[0] == b'\n' {
+                // completes the `\r\n`, whose line break was already counted
+                chunk = &chunk[1..];
+            }
+        }
+        if self.held.is_empty() {
+            self.process(chunk, false);
+        } else {
+            let mut dat = core::mem::take(&mut self.held);
+            dat.extend_from_slice(chunk);
+            self.process(&dat, false);
+        }
+    }
+
+    /// marks the end of the input, which resolves a trailing `\r`
+    /// (see the [type documentation](Self)).
+    pub fn finish(&mut self) {
+        if self.held.last() == Some(&b'\r') {
+            self.finished_cr = true;
+        }
+        let dat = core::mem::take(&mut self.held);
+        self.process(&dat, true);
+    }
+
+    fn process(&mut self, dat: &[u8], last: bool) {
+        let hold = if last { 0 } else { self.hold_len(dat) };
+        let (body, held) = dat.split_at(dat.len() - hold);
+        let mut start = 0;
+        for (_, end) in self.terminators.find_iter(body) {
+            self.line += 1;
+            self.column = 0;
+            self.line_buf.clear();
+            start = end;
+        }
+        let rest = &body[start..];
+        if self.unit.needs_context() {
+            self.line_buf.extend_from_slice(rest);
+            self.column = self.unit.advance(&self.line_buf, 0, self.tab_width);
+        } else {
+            self.column = self.unit.advance(rest, self.column, self.tab_width);
+        }
+        self.held.extend_from_slice(held);
+    }
+
+    /// returns the number of bytes at the end of `dat`
+    /// which can't be processed before the following ones are known
+    fn hold_len(&self, dat: &[u8]) -> usize {
+        if dat.last() == Some(&b'\r') {
+            return usize::from(self.terminators.lone_cr());
+        }
+        incomplete_len(dat)
+    }
+}
+
+/// A reader adapter which tracks the position of the data read through it.
+///
+/// The position is the one after all bytes returned by the inner reader so
+/// far; when the inner reader reports the end of its data, the tracker is
+/// [finished](PosTrackerStream::finish). Reading may continue after that,
+/// e.g. when the inner reader follows a growing file. To track the position
+/// of data which was actually consumed, put a buffered reader around this
+/// adapter and not the other way round, and use
+/// [`read_line`](std::io::BufRead::read_line) or similar to only consume
+/// whole lines.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct TrackingReader<R> {
+    inner: R,
+    tracker: PosTrackerStream,
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> TrackingReader<R> {
+    /// wraps `inner`, tracking columns in bytes
+    #[inline]
+    pub fn new(inner: R) -> Self {
+        Self::with_tracker(inner, PosTrackerStream::new())
+    }
+
+    /// wraps `inner`, using a configured tracker
+    #[inline]
+    pub fn with_tracker(inner: R, tracker: PosTrackerStream) -> Self {
+        Self { inner, tracker }
+    }
+
+    #[inline(always)]
+    pub fn tracker(&self) -> &PosTrackerStream {
+        &self.tracker
+    }
+
+    #[inline(always)]
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// returns the inner reader; reading from it directly
+    /// bypasses the tracker.
+    #[inline(always)]
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> std::io::Read for TrackingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            self.tracker.finish();
+        } else {
+            self.tracker.feed(&buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+/// A writer adapter which tracks the position of the output written through
+/// it, e.g. to record where generated items end up.
+///
+/// It implements [`fmt::Write`], and `std::io::Write` with the `std` feature.
+/// A `\r` written last is subject to the same restriction as described for
+/// [`PosTrackerStream`].
+#[derive(Debug)]
+pub struct TrackingWriter<W> {
+    inner: W,
+    tracker: PosTrackerStream,
+    marks: Vec<Position>,
+}
+
+impl<W> TrackingWriter<W> {
+    /// wraps `inner`, tracking columns in bytes
+    #[inline]
+    pub fn new(inner: W) -> Self {
+        Self::with_tracker(inner, PosTrackerStream::new())
+    }
+
+    /// wraps `inner`, using a configured tracker
+    #[inline]
+    pub fn with_tracker(inner: W, tracker: PosTrackerStream) -> Self {
+        Self {
+            inner,
+            tracker,
+            marks: Vec::new(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn tracker(&self) -> &PosTrackerStream {
+        &self.tracker
+    }
+
+    /// returns the position the next output will be written at
+    #[inline]
+    pub fn position(&self) -> Position {
+        self.tracker.position()
+    }
+
+    /// records the current position, and returns it
+    #[inline]
+    pub fn mark(&mut self) -> Position {
+        let pos = self.position();
+        self.marks.push(pos);
+        pos
+    }
+
+    /// returns the positions recorded using [`mark`](Self::mark), in order
+    #[inline(always)]
+    pub fn marks(&self) -> &[Position] {
+        &self.marks
+    }
+
+    /// returns the recorded positions, and clears them
+    #[inline]
+    pub fn take_marks(&mut self) -> Vec<Position> {
+        core::mem::take(&mut self.marks)
+    }
+
+    #[inline(always)]
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// returns the inner writer; writing to it directly
+    /// bypasses the tracker.
+    #[inline(always)]
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: fmt::Write> fmt::Write for TrackingWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_str(s)?;
+        self.tracker.feed(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> std::io::Write for TrackingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.tracker.feed(&buf[..n]);
+        Ok(n)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LineCache;
+
+    #[test]
+    fn chunked() {
+        // a simple LCG, to get reproducible pseudo-random chunks
+        let mut seed = 0x2545_F491_u32;
+        let mut rand = |n: usize| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 8) as usize % n
+        };
+        const SRC: &str = "ab\r\n\tcd\n\ref\u{2028}漢字\u{85}\te\u{301}😀\r\r\n\x0Cx\r";
+        #[allow(unused_mut)]
+        let mut units = alloc::vec![ColumnUnit::Byte, ColumnUnit::Char, ColumnUnit::Utf16];
+        #[cfg(feature = "unicode-width")]
+        units.push(ColumnUnit::Width);
+        #[cfg(feature = "unicode-segmentation")]
+        units.push(ColumnUnit::Grapheme);
+
+        for t in [
+            LineTerminators::Lf,
+            LineTerminators::LfCrLf,
+            LineTerminators::LfCrCrLf,
+            LineTerminators::Unicode,
+        ] {
+            let lc = LineCache::with_terminators(SRC, t);
+            for &unit in &units {
+                for tab_width in [0, 4] {
+                    for _ in 0..50 {
+                        let mut tracker = PosTrackerStream::with_unit(unit);
+                        tracker.set_terminators(t);
+                        tracker.set_tab_width(tab_width);
+                        let mut pos = 0;
+                        while pos < SRC.len() {
+                            let next = (pos + 1 + rand(4)).min(SRC.len());
+                            tracker.feed(&SRC.as_bytes()[pos..next]);
+                            pos = next;
+                            assert_eq!(tracker.offset(), pos);
+                            let undecided = t.lone_cr()
+                                && SRC.as_bytes()[pos - 1] == b'\r'
+                                && SRC.as_bytes().get(pos) != Some(&b'\n');
+                            if !undecided {
+                                let (line, col, _) = lc.run_tabbed(SRC, pos, unit, tab_width);
+                                assert_eq!(
+                                    (tracker.line(), tracker.column()),
+                                    (line, col),
+                                    "{:?} {:?} at {}",
+                                    t,
+                                    unit,
+                                    pos
+                                );
+                            }
+                        }
+                        tracker.finish();
+                        let (line, col, _) = lc.run_tabbed(SRC, SRC.len(), unit, tab_width);
+                        assert_eq!((tracker.line(), tracker.column()), (line, col));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn trailing_cr() {
+        let mut tracker = PosTrackerStream::new();
+        tracker.set_terminators(LineTerminators::LfCrCrLf);
+        tracker.feed(b"ab\r");
+        // might still become a `\r\n`
+        assert_eq!((tracker.line(), tracker.column()), (0, 3));
+        tracker.feed(b"\n");
+        assert_eq!((tracker.line(), tracker.column()), (1, 0));
+        tracker.feed(b"c\r");
+        tracker.finish();
+        assert_eq!(
+            (tracker.offset(), tracker.line(), tracker.column()),
+            (6, 2, 0)
+        );
+
+        // the input grows after the end was reached,
+        // the `\n` completes the `\r\n` counted by `finish`
+        tracker.finish();
+        tracker.feed(b"");
+        tracker.feed(b"\nd");
+        let lines = LineCache::with_terminators("ab\r\nc\r\nd", LineTerminators::LfCrCrLf);
+        assert_eq!(
+            (tracker.line(), tracker.column()),
+            lines.run(tracker.offset())
+        );
+        assert_eq!((tracker.line(), tracker.column()), (2, 1));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn reader() {
+        use std::io::{BufRead, BufReader, Read};
+
+        /// returns the data in chunks of at most 3 bytes
+        struct Chunks<'a>(&'a [u8]);
+        impl Read for Chunks<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let n = self.0.len().min(buf.len()).min(3);
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
+            }
+        }
+
+        let mut tracker = PosTrackerStream::with_unit(ColumnUnit::Char);
+        tracker.set_terminators(LineTerminators::LfCrCrLf);
+        let mut reader = TrackingReader::with_tracker(Chunks("äö\r\nü\r".as_bytes()), tracker);
+        let mut buf = [0; 4];
+        assert_eq!(reader.read(&mut buf).unwrap(), 3);
+        assert_eq!(reader.tracker().column(), 2);
+        let mut rest = std::vec::Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        let t = reader.tracker();
+        assert_eq!((t.offset(), t.line(), t.column()), (9, 2, 0));
+
+        // following a log file, the end is only temporary
+        let mut tracker = PosTrackerStream::new();
+        tracker.set_terminators(LineTerminators::LfCrCrLf);
+        let mut reader = TrackingReader::with_tracker(Chunks(b"a\r"), tracker);
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!((reader.tracker().line(), reader.tracker().column()), (1, 0));
+        reader.get_mut().0 = b"\nb";
+        reader.read_to_end(&mut rest).unwrap();
+        let t = reader.tracker();
+        assert_eq!((t.offset(), t.line(), t.column()), (4, 1, 1));
+
+        // with a buffered reader on top, lines can be read one by one
+        let reader = TrackingReader::new(Chunks(b"ab\ncd\n"));
+        let mut reader = BufReader::with_capacity(2, reader);
+        let mut line = std::string::String::new();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "ab\n");
+        assert_eq!(reader.get_ref().tracker().line(), 1);
+    }
+
+    #[test]
+    fn writer() {
+        use core::fmt::Write;
+
+        let mut tracker = PosTrackerStream::with_unit(ColumnUnit::Char);
+        tracker.set_tab_width(4);
+        let mut w = TrackingWriter::with_tracker(alloc::string::String::new(), tracker);
+        writeln!(w, "fn größe() {{").unwrap();
+        w.write_str("\t").unwrap();
+        let call = w.mark();
+        write!(w, "foo({});\n}}", 1).unwrap();
+        let end = w.mark();
+        assert_eq!(w.get_ref(), "fn größe() {\n\tfoo(1);\n}");
+        assert_eq!(call, Position::new(16, 1, 4));
+        assert_eq!(end, Position::new(25, 2, 1));
+        assert_eq!(w.marks(), [call, end]);
+        assert_eq!(w.take_marks(), [call, end]);
+        assert!(w.marks().is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn io_writer() {
+        use std::io::Write;
+
+        /// accepts at most 2 bytes per write
+        struct Short(std::vec::Vec<u8>);
+        impl Write for Short {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                let n = buf.len().min(2);
+                self.0.extend_from_slice(&buf[..n]);
+                Ok(n)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut w = TrackingWriter::new(Short(std::vec::Vec::new()));
+        w.write_all("a\nä\nbc".as_bytes()).unwrap();
+        w.flush().unwrap();
+        assert_eq!(w.position(), Position::new(7, 2, 2));
+        assert_eq!(w.into_inner().0, "a\nä\nbc".as_bytes());
+    }
+}
@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+/// The set of byte sequences which end a line.
+///
+/// In every policy which knows about `\r\n`, it is a single line break.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum LineTerminators {
+    /// only `\n`
+    #[default]
+    Lf,
+    /// `\n` and `\r\n`; a lone `\r` is an ordinary character
+    LfCrLf,
+    /// `\n`, `\r\n` and a lone `\r` (e.g. classic Mac OS files)
+    LfCrCrLf,
+    /// everything Unicode recommends to treat as a line terminator:
+    /// `\n`, `\r\n`, `\r`, VT (U+000B), FF (U+000C), NEL (U+0085),
+    /// LINE SEPARATOR (U+2028) and PARAGRAPH SEPARATOR (U+2029)
+    Unicode,
+}
+
+impl LineTerminators {
+    /// returns `true` if a lone `\r` ends a line
+    #[inline]
+    pub(crate) fn lone_cr(self) -> bool {
+        matches!(self, LineTerminators::LfCrCrLf | LineTerminators::Unicode)
+    }
+
+    /// if a line terminator starts at the beginning of `dat`,
+    /// returns its length in bytes.
+    pub fn match_start(self, dat: &[u8]) -> Option<usize> {
+        use LineTerminators as T;
+        match (self, dat) {
+            (_, [b'\n', ..]) => Some(1),
+            (T::Lf, _) => None,
+            (_, [b'\r', b'\n', ..]) => Some(2),
+            (T::LfCrCrLf | T::Unicode, [b'\r', ..]) => Some(1),
+            (T::Unicode, [0x0B | 0x0C, ..]) => Some(1),
+            (T::Unicode, [0xC2, 0x85, ..]) => Some(2),
+            (T::Unicode, [0xE2, 0x80, 0xA8 | 0xA9, ..]) => Some(3),
+            _ => None,
+        }
+    }
+
+    /// returns an iterator over the `(start, end)` offsets
+    /// of all line terminators in `dat`.
+    pub fn find_iter(self, dat: &[u8]) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let mut i = 0;
+        core::iter::from_fn(move || {
+            while i < dat.len() {
+                let start = i;
+                if let Some(len) = self.match_start(&dat[i..]) {
+                    i += len;
+                    return Some((start, i));
+                }
+                i += 1;
+            }
+            None
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn find_terminators() {
+        const SRC: &str = "a\nb\r\nc\rd\u{2028}e\u{85}f\x0Cg";
+        let find = |t: LineTerminators| t.find_iter(SRC.as_bytes()).collect::<Vec<_>>();
+        assert_eq!(find(LineTerminators::Lf), [(1, 2), (4, 5)]);
+        assert_eq!(find(LineTerminators::LfCrLf), [(1, 2), (3, 5)]);
+        assert_eq!(find(LineTerminators::LfCrCrLf), [(1, 2), (3, 5), (6, 7)]);
+        assert_eq!(
+            find(LineTerminators::Unicode),
+            [(1, 2), (3, 5), (6, 7), (8, 11), (12, 14), (15, 16)]
+        );
+    }
+}
@@ -0,0 +1,295 @@
+// SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
+
+/// The unit in which columns are counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ColumnUnit {
+    /// UTF-8 code units, i.e. bytes
+    #[default]
+    Byte,
+    /// Unicode scalar values, i.e. `char`s
+    Char,
+    /// UTF-16 code units; characters outside of the basic multilingual
+    /// plane (encoded as surrogate pairs) count as 2
+    Utf16,
+    /// display width in a terminal, following the usual `wcwidth` rules:
+    /// East Asian Wide and Fullwidth characters count as 2,
+    /// combining marks, zero-width joiners and control characters as 0
+    #[cfg(feature = "unicode-width")]
+    Width,
+    /// extended grapheme clusters as defined by UAX #29, i.e. what
+    /// is usually perceived as a single character (e.g. `a` followed by
+    /// a combining diaeresis, or a flag emoji)
+    ///
+    /// Positions are expected to lie on cluster boundaries.
+    #[cfg(feature = "unicode-segmentation")]
+    Grapheme,
+}
+
+impl ColumnUnit {
+    /// returns the number of columns `c` occupies
+    #[inline]
+    pub fn char_width(self, c: char) -> usize {
+        match self {
+            ColumnUnit::Byte => c.len_utf8(),
+            ColumnUnit::Char => 1,
+            ColumnUnit::Utf16 => c.len_utf16(),
+            // control characters have no defined width, they aren't printed
+            #[cfg(feature = "unicode-width")]
+            ColumnUnit::Width => unicode_width::UnicodeWidthChar::width(c).unwrap_or(0),
+            // a lone char always forms a single cluster
+            #[cfg(feature = "unicode-segmentation")]
+            ColumnUnit::Grapheme => 1,
+        }
+    }
+
+    /// returns `true` if the number of columns of a string
+    /// can't be computed by looking at each `char` on its own.
+    #[inline]
+    pub(crate) fn needs_context(self) -> bool {
+        match self {
+            #[cfg(feature = "unicode-segmentation")]
+            ColumnUnit::Grapheme => true,
+            _ => false,
+        }
+    }
+
+    /// returns `true` if the columns of a `char` are only known once all of
+    /// its bytes are, see [`count`](Self::count).
+    #[inline]
+    pub(crate) fn needs_whole_chars(self) -> bool {
+        match self {
+            #[cfg(feature = "unicode-width")]
+            ColumnUnit::Width => true,
+            #[cfg(feature = "unicode-segmentation")]
+            ColumnUnit::Grapheme => true,
+            _ => false,
+        }
+    }
+
+    /// splits `s` into the smallest pieces which are counted as a whole,
+    /// yielding `(byte length, columns)` for each of them.
+    pub(crate) fn segments(self, s: &str) -> Segments<'_> {
+        match self {
+            #[cfg(feature = "unicode-segmentation")]
+            ColumnUnit::Grapheme => Segments::Graphemes(
+                unicode_segmentation::UnicodeSegmentation::graphemes(s, true),
+            ),
+            _ => Segments::Chars(s.chars(), self),
+        }
+    }
+
+    /// returns the number of columns `dat` occupies
+    ///
+    /// `dat` is expected to be UTF-8, multi-byte sequences are
+    /// attributed to their leading byte. An invalid sequence counts
+    /// like a replacement character.
+    ///
+    /// The display width of a `char` and the cluster it belongs to aren't
+    /// known before all of its bytes are, so with `Width` and `Grapheme`
+    /// an incomplete sequence at the end of `dat` counts as 0.
+    pub fn count(self, dat: &[u8]) -> usize {
+        // continuation bytes
+        let is_lead = |&&b: &&u8| b & 0xC0 != 0x80;
+        match self {
+            ColumnUnit::Byte => dat.len(),
+            ColumnUnit::Char => dat.iter().filter(is_lead).count(),
+            // leading bytes of 4-byte sequences need a surrogate pair
+            ColumnUnit::Utf16 => dat
+                .iter()
+                .filter(is_lead)
+                .map(|&b| if b >= 0xF0 { 2 } else { 1 })
+                .sum(),
+            #[cfg(feature = "unicode-width")]
+            ColumnUnit::Width => dat[..dat.len() - incomplete_len(dat)]
+                .utf8_chunks()
+                .map(|chunk| {
+                    let valid: usize = chunk.valid().chars().map(|c| self.char_width(c)).sum();
+                    valid + usize::from(!chunk.invalid().is_empty())
+                })
+                .sum(),
+            #[cfg(feature = "unicode-segmentation")]
+            ColumnUnit::Grapheme => dat[..dat.len() - incomplete_len(dat)]
+                .utf8_chunks()
+                .map(|chunk| {
+                    let valid =
+                        unicode_segmentation::UnicodeSegmentation::graphemes(chunk.valid(), true)
+                            .count();
+                    valid + usize::from(!chunk.invalid().is_empty())
+                })
+                .sum(),
+        }
+    }
+
+    /// returns the column reached after `dat`, when starting at column `col`.
+    ///
+    /// Tabs advance to the next multiple of `tab_width`
+    /// (see [`next_tab_stop`]), all other bytes are counted like in
+    /// [`count`](Self::count).
+    pub fn advance(self, dat: &[u8], col: usize, tab_width: usize) -> usize {
+        let mut parts = dat.split(|&i| i == b'\t');
+        // `split` always yields at least one part
+        let first = parts.next().map_or(0, |part| self.count(part));
+        parts.fold(col + first, |col, part| {
+            next_tab_stop(col, tab_width) + self.count(part)
+        })
+    }
+
+    /// like [`advance`](Self::advance), for input which may still continue.
+    ///
+    /// Also returns the offset into `dat` from which the columns have to be
+    /// counted again once more input is known, together with the column
+    /// there: the start of an incomplete `char` at the end, or of the last
+    /// cluster, which following `char`s may extend.
+    pub(crate) fn advance_partial(
+        self,
+        dat: &[u8],
+        col: usize,
+        tab_width: usize,
+    ) -> (usize, usize, usize) {
+        let column = self.advance(dat, col, tab_width);
+        if !self.needs_whole_chars() {
+            return (column, dat.len(), column);
+        }
+        let complete = dat.len() - incomplete_len(dat);
+        // tabs and invalid sequences always end a cluster
+        let last = match dat[..complete].utf8_chunks().last() {
+            Some(chunk) if self.needs_context() && chunk.invalid().is_empty() => {
+                let s = chunk.valid();
+                let s = &s[s.rfind('\t').map_or(0, |i| i + 1)..];
+                complete - self.segments(s).next_back().map_or(0, |(len, _)| len)
+            }
+            _ => complete,
+        };
+        if last == dat.len() {
+            return (column, last, column);
+        }
+        (column, last, self.advance(&dat[..last], col, tab_width))
+    }
+}
+
+/// returns the next tab stop after `col`. A `tab_width` of 0
+/// disables tab expansion, in which case the tab counts as
+/// a single column.
+#[inline]
+pub fn next_tab_stop(col: usize, tab_width: usize) -> usize {
+    match tab_width {
+        0 => col + 1,
+        _ => (col / tab_width + 1) * tab_width,
+    }
+}
+
+/// returns the length of an incomplete UTF-8 sequence at the end of `dat`,
+/// which may still be completed by the following bytes
+pub(crate) fn incomplete_len(dat: &[u8]) -> usize {
+    for back in 1..=dat.len().min(3) {
+        let seq_len = match dat[dat.len() - back] {
+            // continuation byte
+            0x80..=0xBF => continue,
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 1,
+        };
+        return if seq_len > back { back } else { 0 };
+    }
+    0
+}
+
+/// An iterator over the pieces of a string which are counted as a whole,
+/// see [`ColumnUnit::segments`].
+pub(crate) enum Segments<'a> {
+    Chars(core::str::Chars<'a>, ColumnUnit),
+    #[cfg(feature = "unicode-segmentation")]
+    Graphemes(unicode_segmentation::Graphemes<'a>),
+}
+
+impl Iterator for Segments<'_> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<(usize, usize)> {
+        match self {
+            Segments::Chars(it, unit) => it.next().map(|c| (c.len_utf8(), unit.char_width(c))),
+            #[cfg(feature = "unicode-segmentation")]
+            Segments::Graphemes(it) => it.next().map(|g| (g.len(), 1)),
+        }
+    }
+}
+
+impl DoubleEndedIterator for Segments<'_> {
+    fn next_back(&mut self) -> Option<(usize, usize)> {
+        match self {
+            Segments::Chars(it, unit) => it.next_back().map(|c| (c.len_utf8(), unit.char_width(c))),
+            #[cfg(feature = "unicode-segmentation")]
+            Segments::Graphemes(it) => it.next_back().map(|g| (g.len(), 1)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_units() {
+        // 'a', 'ä' (2 bytes), '€' (3 bytes), '𝄞' (4 bytes, surrogate pair)
+        const SRC: &str = "aä€𝄞";
+        assert_eq!(ColumnUnit::Byte.count(SRC.as_bytes()), 10);
+        assert_eq!(ColumnUnit::Char.count(SRC.as_bytes()), 4);
+        assert_eq!(ColumnUnit::Utf16.count(SRC.as_bytes()), 5);
+        for unit in [ColumnUnit::Byte, ColumnUnit::Char, ColumnUnit::Utf16] {
+            let by_char: usize = SRC.chars().map(|c| unit.char_width(c)).sum();
+            assert_eq!(unit.count(SRC.as_bytes()), by_char, "{:?}", unit);
+        }
+    }
+
+    #[cfg(feature = "unicode-width")]
+    #[test]
+    fn count_width() {
+        let w = |s: &str| ColumnUnit::Width.count(s.as_bytes());
+        assert_eq!(w("abc"), 3);
+        // East Asian Wide
+        assert_eq!(w("漢字"), 4);
+        // Fullwidth
+        assert_eq!(w("ＡＢ"), 4);
+        // 'a' + combining diaeresis
+        assert_eq!(w("a\u{308}"), 1);
+        // zero-width joiner
+        assert_eq!(w("\u{200D}"), 0);
+        assert_eq!(w("🦀"), 2);
+        // invalid UTF-8 is shown as a replacement character
+        assert_eq!(ColumnUnit::Width.count(b"a\xFFb"), 3);
+        // the width of an incomplete `char` isn't known yet
+        assert_eq!(ColumnUnit::Width.count(&"a漢".as_bytes()[..3]), 1);
+        assert_eq!(ColumnUnit::Width.count(&"漢".as_bytes()[1..]), 2);
+    }
+
+    #[cfg(feature = "unicode-segmentation")]
+    #[test]
+    fn count_graphemes() {
+        let g = |s: &str| ColumnUnit::Grapheme.count(s.as_bytes());
+        assert_eq!(g("abc"), 3);
+        // 'a' + combining diaeresis
+        assert_eq!(g("a\u{308}o"), 2);
+        // regional indicators forming the German flag
+        assert_eq!(g("\u{1F1E9}\u{1F1EA}!"), 2);
+        // family emoji joined by zero-width joiners
+        assert_eq!(g("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}"), 1);
+        // CRLF is a single cluster
+        assert_eq!(g("\r\n"), 1);
+        let segs: alloc::vec::Vec<_> = ColumnUnit::Grapheme.segments("a\u{308}b").collect();
+        assert_eq!(segs, [(3, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn tab_stops() {
+        assert_eq!(next_tab_stop(0, 4), 4);
+        assert_eq!(next_tab_stop(3, 4), 4);
+        assert_eq!(next_tab_stop(4, 4), 8);
+        assert_eq!(next_tab_stop(5, 0), 6);
+        let unit = ColumnUnit::Char;
+        assert_eq!(unit.advance(b"\tx", 0, 8), 9);
+        assert_eq!(unit.advance("ab\tä\t".as_bytes(), 0, 4), 8);
+        assert_eq!(unit.advance(b"ab\t", 3, 4), 8);
+        assert_eq!(unit.advance(b"ab\t", 0, 0), 3);
+    }
+}